@@ -0,0 +1,90 @@
+use chrono::{prelude::Local, NaiveDateTime};
+use serde::{Serialize, Deserialize};
+use crate::utils::ListDiff;
+use std::{
+    fs,
+    io::{Error, Write},
+    path::Path,
+};
+
+/// One change to the task list, stored as a line of JSON
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub struct AuditEvent {
+    /// `open`, `close` or `remove`
+    pub op: String,
+    pub id: i32,
+    pub timestamp: NaiveDateTime
+}
+
+/// Audit log kept next to a task file, `tasks.json` logs to `tasks.audit.jsonl`
+pub fn audit_path(fpath: &str) -> String {
+    Path::new(fpath).with_extension("audit.jsonl").to_string_lossy().to_string()
+}
+
+/// Events for the tasks added, closed and removed in a diff
+pub fn events_from(changes: &ListDiff) -> Vec<AuditEvent> {
+    let timestamp = Local::now().naive_local();
+    let ops = [("open", &changes.added), ("close", &changes.closed), ("remove", &changes.removed)];
+
+    ops.into_iter()
+        .flat_map(|(op, ids)| ids.iter().map(move |id| AuditEvent { op: op.to_string(), id: *id, timestamp }))
+        .collect()
+}
+
+/// Append events to the log, one JSON object per line
+pub fn append(fpath: &str, events: &[AuditEvent]) -> Result<(), Error> {
+    if events.is_empty() {
+        return Ok(())
+    }
+
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(fpath)?;
+    for event in events {
+        writeln!(file, "{}", serde_json::to_string(event)?)?;
+    }
+    Ok(())
+}
+
+/// Append events, warning instead of failing when the log can't be written
+pub fn record(fpath: &str, events: &[AuditEvent]) {
+    if let Err(e) = append(fpath, events) {
+        eprintln!("Could not write audit log {}: {}", fpath, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_next_to_task_file() {
+        assert_eq!(audit_path("tasks.json"), "tasks.audit.jsonl");
+        assert_eq!(audit_path("dir/work.json"), "dir/work.audit.jsonl");
+    }
+
+    #[test]
+    fn close_appends_event() {
+        let mut list = crate::utils::List::new();
+        let id = list.add_task("Ship it", None).unwrap();
+        let before = list.clone();
+        list.close_task(id).unwrap();
+
+        let fpath = std::env::temp_dir().join(format!("todo-{}-close.audit.jsonl", std::process::id()));
+        let fpath = fpath.to_string_lossy();
+        let started = Local::now().naive_local();
+        append(&fpath, &events_from(&crate::utils::diff(&before, &list))).unwrap();
+
+        let content = fs::read_to_string(fpath.as_ref()).unwrap();
+        let events = content.lines().map(|l| serde_json::from_str::<AuditEvent>(l).unwrap()).collect::<Vec<AuditEvent>>();
+        assert_eq!(events.len(), 1);
+        assert_eq!((events[0].op.as_str(), events[0].id), ("close", id));
+        assert!(events[0].timestamp >= started && events[0].timestamp <= Local::now().naive_local());
+        fs::remove_file(fpath.as_ref()).unwrap();
+    }
+
+    #[test]
+    fn unwritable_log_is_skipped() {
+        let events = events_from(&ListDiff { closed: vec![1], ..ListDiff::default() });
+        assert!(append("does/not/exist/tasks.audit.jsonl", &events).is_err());
+        record("does/not/exist/tasks.audit.jsonl", &events);
+    }
+}
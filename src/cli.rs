@@ -0,0 +1,1159 @@
+use crate::audit;
+use crate::command::{parse_command, CloseTarget, Command, ListFilter};
+use crate::config::{color_enabled, passphrase, Config, Theme};
+use crate::utils::{self, List};
+use chrono::{Datelike, Duration};
+use std::io::{self, BufRead, Read, Write};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::Instant;
+
+/// Read one line for a follow-up question, empty on EOF or error
+fn read_answer<R: BufRead>(input: &mut R, answer: &mut String) {
+    if let Err(e) = input.read_line(answer) {
+        println!("Error reading input: {}", e);
+    }
+}
+
+/// Whether the answer to a yes/no question is `y`, EOF or a read error count as no
+fn confirmed<R: BufRead>(input: &mut R) -> bool {
+    let mut answer = String::new();
+    match input.read_line(&mut answer) {
+        Ok(0) => false,
+        Ok(_) => answer.trim() == "y",
+        Err(e) => {
+            println!("Error reading input: {}", e);
+            false
+        }
+    }
+}
+
+/// Print a line to the app's output, see `App::out`
+macro_rules! say {
+    ($app:expr, $($arg:tt)*) => {
+        { let _ = writeln!($app.out, $($arg)*); }
+    };
+}
+
+/// Whether an answer to the deadline question is rather the next command, as piped input may skip the answer.
+/// This is a guess: anything that isn't a deadline but parses as a command counts, so answering `help`
+/// or `list` runs that command and leaves the task without a deadline.
+fn is_command_not_deadline(answer: &str, day_start_hour: u32) -> bool {
+    let answer = answer.trim();
+    !answer.is_empty()
+        && !answer.starts_with('+')
+        && utils::parse_deadline(answer.to_string(), day_start_hour).is_none()
+        && parse_command(answer, day_start_hour).is_ok()
+}
+
+/// Input lines read by a background thread, so the session can act while waiting for the next one
+pub struct LineChannel {
+    rx: Receiver<String>,
+    pending: Vec<u8>,
+    pos: usize
+}
+
+impl LineChannel {
+    pub fn spawn<R: BufRead + Send + 'static>(mut input: R) -> Self {
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || loop {
+            let mut line = String::new();
+            match input.read_line(&mut line) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => if tx.send(line).is_err() {
+                    break
+                }
+            }
+        });
+        Self { rx, pending: Vec::new(), pos: 0 }
+    }
+
+    /// Next whole line, waiting at most `timeout` when given
+    fn next_line(&mut self, timeout: Option<std::time::Duration>) -> Result<String, RecvTimeoutError> {
+        match timeout {
+            Some(timeout) => self.rx.recv_timeout(timeout),
+            None => self.rx.recv().map_err(|_| RecvTimeoutError::Disconnected)
+        }
+    }
+}
+
+impl Read for LineChannel {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+/// Follow-up answers block until the next line, an empty buffer means the input ended
+impl BufRead for LineChannel {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos >= self.pending.len() {
+            if let Ok(line) = self.rx.recv() {
+                self.pending = line.into_bytes();
+                self.pos = 0;
+            }
+        }
+        Ok(&self.pending[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.pending.len());
+    }
+}
+
+/// How the last command ended
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Outcome {
+    Success,
+    /// The command was understood but could not be carried out
+    Failed,
+    /// Unknown command or bad arguments
+    Usage
+}
+
+impl Outcome {
+    /// Process exit code in single-command mode
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Outcome::Success => 0,
+            Outcome::Failed => 1,
+            Outcome::Usage => 2
+        }
+    }
+}
+
+/// Interactive session over a task list
+pub struct App {
+    pub list: List,
+    pub config: Config,
+    pub theme: Theme,
+    /// Show the prompt before each command
+    pub interactive: bool,
+    /// Don't save on exit
+    pub read_only: bool,
+    /// Result of the last command run
+    pub outcome: Outcome,
+    /// Runs the `on_complete` hook
+    pub runner: Box<dyn utils::CommandRunner>,
+    /// Color errors and reminders, decided once by `color_enabled`
+    pub color: bool,
+    /// Print to stderr how long each stage took, `TODO_DEBUG`
+    pub debug: bool,
+    /// Where the app's own messages go, stdout unless replaced, e.g. by tests.
+    /// Listings printed by `utils` still go to stdout.
+    pub out: Box<dyn Write>,
+    /// List as it was when the session started
+    snapshot: List,
+    /// Line read as an answer that turned out to be the next command
+    pending_line: Option<String>
+}
+
+impl App {
+    pub fn new(mut list: List, config: Config) -> Self {
+        list.set_day_start_hour(config.day_start_hour);
+        Self {
+            snapshot: list.clone(),
+            list,
+            config,
+            theme: Theme::default(),
+            interactive: false,
+            read_only: false,
+            outcome: Outcome::Success,
+            runner: Box::new(utils::ShellRunner),
+            color: color_enabled(),
+            debug: false,
+            out: Box::new(io::stdout()),
+            pending_line: None
+        }
+    }
+
+    /// Cheer a running streak, once per command however many tasks it closed
+    fn cheer(&mut self) {
+        if let Some(message) = utils::streak_message(self.list.current_streak(), &self.theme.streak) {
+            say!(self, "{}", message);
+        }
+    }
+
+    /// Run the `on_complete` hook for a task that was just closed
+    fn completed(&mut self, task_id: i32) {
+        if let Some(entry) = self.list.find(task_id) {
+            utils::run_on_complete(self.config.on_complete.as_deref(), entry.get_name(), self.runner.as_ref());
+        }
+    }
+
+    /// Keep the closing note, then report what closing the task set off, the streak is left to `cheer`
+    fn closed(&mut self, task_id: i32, note: Option<&str>) {
+        if let Some(note) = note {
+            if let Err(e) = self.list.add_comment(task_id, note) {
+                self.fail(e);
+            }
+        }
+        self.completed(task_id);
+        for unblocked in self.list.unblocked_by(task_id) {
+            say!(self, "Task {} is no longer blocked", unblocked);
+        }
+    }
+
+    /// Remaining work shown after a close, unless `--quiet` was given
+    fn close_summary(&self, quiet: bool) -> Option<String> {
+        if quiet {
+            None
+        }
+        else {
+            Some(utils::summary_line(&self.list))
+        }
+    }
+
+    /// Warn when more tasks are open than the configured limit
+    fn warn_wip_limit(&mut self) {
+        let open = self.list.pending().len();
+        if utils::over_wip_limit(open, self.config.wip_limit) {
+            say!(self,
+                "You have {} open tasks (limit {}) — consider finishing before adding.",
+                open, self.config.wip_limit.unwrap_or_default()
+            );
+        }
+    }
+
+    /// Report a command error
+    fn fail<E: std::fmt::Display>(&mut self, e: E) {
+        say!(self, "{}", utils::colorize(&e.to_string(), "31", self.color));
+        self.outcome = Outcome::Failed;
+    }
+
+    /// Report a usage error
+    fn usage<E: std::fmt::Display>(&mut self, e: E) {
+        say!(self, "{}", e);
+        self.outcome = Outcome::Usage;
+    }
+
+    fn grace(&self) -> Duration {
+        Duration::hours(self.config.grace_hours as i64)
+    }
+
+    /// Summary and due reminders shown when an interactive session starts
+    fn banner(&mut self) {
+        if self.interactive {
+            self.list.check_overdues_after(self.grace());
+            say!(self, "{}", utils::summary_line(&self.list));
+            if let Some(saved) = self.list.get_last_modified() {
+                say!(self, "Last modified {}", utils::humanize_ago(saved, chrono::Local::now().naive_local()));
+            }
+            for el in self.list.reminders_at(chrono::Local::now().naive_local()) {
+                let reminder = format!("Reminder: {} ({})", el.render(&self.theme), el.remaining_display(self.config.day_start_hour));
+                say!(self, "{}", utils::colorize(&reminder, "33", self.color));
+            }
+        }
+    }
+
+    fn show_prompt(&mut self) {
+        if self.interactive {
+            let _ = write!(self.out, "{}", self.config.prompt);
+            let _ = self.out.flush();
+        }
+    }
+
+    /// Run commands from `input` until `quit` or EOF, then save
+    pub fn run<R: BufRead>(&mut self, input: &mut R) {
+        let mut line = String::new();
+        self.banner();
+
+        loop {
+            self.show_prompt();
+
+            line.clear();
+            if let Some(pending) = self.pending_line.take() {
+                line = pending;
+            }
+            else {
+                match input.read_line(&mut line) {
+                    // EOF, e.g. Ctrl-D or the end of piped input
+                    Ok(0) => break,
+                    Ok(_) => (),
+                    Err(e) => {
+                        say!(self, "Error reading input: {}", e);
+                        break
+                    }
+                }
+            }
+
+            if !self.timed_execute(&line, input) {
+                break
+            }
+        }
+
+        self.timed_save(input);
+    }
+
+    /// Same as `run`, also writing the task file once no command came for `idle`
+    pub fn run_with_autosave(&mut self, input: &mut LineChannel, idle: std::time::Duration) {
+        let mut last_activity = Instant::now();
+        let mut dirty = false;
+        self.banner();
+        self.show_prompt();
+
+        loop {
+            let timeout = if dirty { Some(idle.saturating_sub(last_activity.elapsed())) } else { None };
+            let next = match self.pending_line.take() {
+                Some(pending) => Ok(pending),
+                None => input.next_line(timeout)
+            };
+            match next {
+                Ok(line) => {
+                    if !self.timed_execute(&line, input) {
+                        break
+                    }
+                    last_activity = Instant::now();
+                    dirty = true;
+                    self.show_prompt();
+                },
+                Err(RecvTimeoutError::Timeout) => {
+                    if utils::should_autosave(last_activity.elapsed(), Some(idle), dirty) {
+                        self.autosave();
+                        dirty = false;
+                    }
+                },
+                Err(RecvTimeoutError::Disconnected) => break
+            }
+        }
+
+        self.timed_save(input);
+    }
+
+    /// Print how long a stage took when `debug` is set
+    pub fn debug_timing(&self, stage: &str, started: Instant) {
+        if self.debug {
+            eprintln!("{}", utils::timing_line(stage, started.elapsed(), self.list.get_size()));
+        }
+    }
+
+    /// `execute`, timed when `debug` is set, the time includes waiting for follow-up answers
+    pub fn timed_execute<R: BufRead>(&mut self, line: &str, input: &mut R) -> bool {
+        let started = Instant::now();
+        let keep_going = self.execute(line, input);
+        self.debug_timing("execute", started);
+        keep_going
+    }
+
+    /// `save`, timed when `debug` is set
+    pub fn timed_save<R: BufRead>(&mut self, input: &mut R) {
+        let started = Instant::now();
+        self.save(input);
+        self.debug_timing("save", started);
+    }
+
+    /// Tasks another instance saved to the task file since this session loaded it.
+    /// A missing file has none, one that can't be read is an error as it may hold some.
+    fn unseen_on_disk(&self) -> Result<usize, io::Error> {
+        match utils::load_list(&self.config.file_path, passphrase().as_deref()) {
+            Ok(on_disk) => Ok(utils::unseen_on_disk(&on_disk, &self.list, &self.snapshot)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(0),
+            Err(e) => Err(e)
+        }
+    }
+
+    /// Why overwriting the task file could lose tasks, if it could
+    fn overwrite_risk(&self) -> Option<String> {
+        match self.unseen_on_disk() {
+            Ok(0) => None,
+            Ok(unseen) => Some(format!("On-disk file has {} not in memory", utils::pluralize(unseen, "task", "tasks"))),
+            Err(e) => Some(format!("On-disk file could not be checked for newer tasks ({})", e))
+        }
+    }
+
+    /// Write the task file without reporting the session's changes, never over tasks it hasn't seen
+    fn autosave(&mut self) {
+        if self.read_only {
+            return
+        }
+        if let Some(risk) = self.overwrite_risk() {
+            say!(self, "Not autosaving: {}", risk);
+            return
+        }
+        if let Err(e) = utils::export(self.list.clone(), &self.config.file_path) {
+            say!(self, "Could not autosave tasks: {}", e);
+        }
+    }
+
+    /// Print what changed this session and write the list to the task file.
+    /// Asks on `input` before overwriting a file that may hold tasks another instance saved meanwhile.
+    pub fn save<R: BufRead>(&mut self, input: &mut R) {
+        let changes = utils::diff(&self.snapshot, &self.list);
+        if !changes.is_empty() {
+            say!(self, "{}", changes);
+        }
+
+        if self.read_only {
+            say!(self, "Read-only mode, changes were not saved");
+            return
+        }
+
+        if let Some(risk) = self.overwrite_risk() {
+            say!(self, "{}; overwrite? (y/n)", risk);
+            let mut resp = String::new();
+            read_answer(input, &mut resp);
+            if resp.trim() != "y" {
+                say!(self, "Changes were not saved");
+                return
+            }
+        }
+
+        let filename = self.config.file_path.clone();
+        if let Err(e) = utils::export(self.list.clone(), &filename) {
+            let fallback = utils::fallback_path(&filename);
+            say!(self, "Could not save tasks to {}: {}; save to {} instead? (y/n)", filename, e, fallback.display());
+            let mut resp = String::new();
+            read_answer(input, &mut resp);
+            if resp.trim() != "y" {
+                say!(self, "Changes were not saved");
+                return
+            }
+
+            match utils::export_fallback(self.list.clone(), &filename) {
+                Ok(written) => say!(self, "Tasks saved to {}", written.display()),
+                Err(e) => say!(self, "Could not save tasks: {}", e)
+            }
+        }
+    }
+
+    /// Run a single command line, logging its changes when the audit log is enabled.
+    /// Returns false once the session should end.
+    pub fn execute<R: BufRead>(&mut self, line: &str, input: &mut R) -> bool {
+        if !self.config.audit_log || self.read_only {
+            return self.dispatch(line, input)
+        }
+
+        let before = self.list.clone();
+        let keep_going = self.dispatch(line, input);
+        let events = audit::events_from(&utils::diff(&before, &self.list));
+        audit::record(&audit::audit_path(&self.config.file_path), &events);
+        keep_going
+    }
+
+    /// Run a single command line, follow-up questions are read from `input`
+    fn dispatch<R: BufRead>(&mut self, line: &str, input: &mut R) -> bool {
+        self.outcome = Outcome::Success;
+        // Every command sees statuses that match the deadlines
+        self.list.check_overdues_after(self.grace());
+
+        match parse_command(line, self.config.day_start_hour) {
+            Ok(command) => self.run_command(command, input),
+            Err(e) => {
+                self.usage(e);
+                true
+            }
+        }
+    }
+
+    /// Carry out a parsed command. Returns false once the session should end.
+    pub fn run_command<R: BufRead>(&mut self, command: Command, input: &mut R) -> bool {
+        match command {
+            Command::Add { name } => {
+                if name.is_empty() {
+                    self.fail(utils::TaskError::EmptyName);
+                }
+                else {
+                    say!(self, "Add deadline? (format: YYYY-MM-DD, +Nd, +Nw or weekday)");
+                    let mut deadline_resp = String::new();
+                    read_answer(input, &mut deadline_resp);
+                    if is_command_not_deadline(&deadline_resp, self.config.day_start_hour) {
+                        self.pending_line = Some(std::mem::take(&mut deadline_resp));
+                    }
+
+                    // Relative deadlines are kept as an offset from creation
+                    let added = if deadline_resp.trim().starts_with('+') {
+                        match utils::parse_offset(&deadline_resp) {
+                            Ok(offset) => self.list.add_task_in(&name, offset),
+                            Err(e) => {
+                                if let utils::TaskError::OffsetTooLong(_) = e {
+                                    say!(self, "{}", e);
+                                }
+                                self.list.add_task(&name, None)
+                            }
+                        }
+                    }
+                    else {
+                        self.list.add_task_checked(&name, utils::parse_deadline(deadline_resp.clone(), self.config.day_start_hour), self.config.strict_deadlines)
+                    };
+
+                    match added {
+                        Ok(task_id) => {
+                            say!(self, "Added task #{}", task_id);
+                            if !deadline_resp.trim().is_empty() && self.list.find(task_id).is_some_and(|e| e.get_deadline().is_none()) {
+                                say!(self, "'{}' is not a valid deadline, the task has none", deadline_resp.trim());
+                            }
+                            if self.list.find(task_id).is_some_and(|e| !e.deadline_is_sane(self.config.day_start_hour)) {
+                                say!(self, "Warning: {}", utils::TaskError::DeadlineBeforeCreation);
+                            }
+                            self.warn_wip_limit();
+                        },
+                        Err(e) => self.fail(e)
+                    }
+                }
+            },
+            Command::Maybe { name } => match self.list.add_someday(&name) {
+                Ok(task_id) => say!(self, "Added task #{} to Someday/Maybe", task_id),
+                Err(e) => self.fail(e)
+            },
+            Command::AddMany => {
+                say!(self, "One task per line, end with an empty line");
+                let mut added = 0;
+                loop {
+                    let mut name = String::new();
+                    read_answer(input, &mut name);
+                    if name.trim().is_empty() {
+                        break
+                    }
+
+                    match self.list.add_task(name.trim(), None) {
+                        Ok(_) => added += 1,
+                        Err(e) => self.fail(e)
+                    }
+                }
+                say!(self, "Added {}", utils::pluralize(added, "task", "tasks"));
+                if added > 0 {
+                    self.warn_wip_limit();
+                }
+            },
+            Command::Help(None) => utils::show_help(),
+            Command::Help(Some(name)) => match utils::command_help(&name) {
+                Some(help) => say!(self, "{}", help),
+                None => self.usage(format!("No help for '{}'", name))
+            },
+            Command::List(filter) => {
+                match filter {
+                    ListFilter::All(sort) => {
+                        let sort = sort.unwrap_or(self.config.default_sort);
+                        utils::list_tasks(&self.list, &self.theme, sort, self.config.hide_done)
+                    },
+                    ListFilter::Priority(priority) => utils::list_by_priority(&self.list, &self.theme, priority),
+                    ListFilter::Someday => utils::list_someday(&self.list, &self.theme),
+                    ListFilter::Oneline => {
+                        let _ = write!(self.out, "{}", utils::render_oneline(&self.list));
+                    }
+                }
+            },
+            Command::Close { target: CloseTarget::Tag(tag), quiet, note, .. } => {
+                let ids = self.list.open_with_tag(&tag);
+                let closed = self.list.close_by_tag(&tag);
+                say!(self, "Closed {} tagged #{}", utils::pluralize(closed, "task", "tasks"), tag);
+                for task_id in ids {
+                    self.closed(task_id, note.as_deref());
+                }
+                self.cheer();
+                if let Some(summary) = self.close_summary(quiet) {
+                    say!(self, "{}", summary);
+                }
+            },
+            Command::Close { target, force, quiet, note } => {
+                let res = match target {
+                    CloseTarget::Id(task_id) if force => self.list.check_id(task_id)
+                        .and_then(|_| self.list.close_task_idempotent(task_id))
+                        .map(|_| task_id),
+                    CloseTarget::Id(task_id) => self.list.check_id(task_id)
+                        .and_then(|_| self.list.close_task(task_id))
+                        .map(|_| task_id),
+                    CloseTarget::Name(name) => self.list.close_by_name(&name),
+                    CloseTarget::Tag(_) => unreachable!("closing by tag is handled above")
+                };
+
+                match res {
+                    Ok(task_id) => {
+                        say!(self, "Closed task {}", task_id);
+                        self.closed(task_id, note.as_deref());
+                        self.cheer();
+                        if let Some(summary) = self.close_summary(quiet) {
+                            say!(self, "{}", summary);
+                        }
+                    },
+                    Err(e) => self.fail(e)
+                }
+            },
+            Command::Start(task_id) => match self.list.start_task(task_id) {
+                Ok(()) => say!(self, "Started task {}", task_id),
+                Err(e) => self.fail(e)
+            },
+            Command::Report(month) => {
+                let (year, month) = month.unwrap_or_else(|| {
+                    let today = utils::logical_today(chrono::Local::now().naive_local(), self.config.day_start_hour);
+                    (today.year(), today.month())
+                });
+                say!(self, "{}", utils::month_report(&self.list, year, month));
+            },
+            Command::Bump(to) => {
+                let bumped = self.list.bump_overdue(to);
+                say!(self, "Moved {}", utils::pluralize(bumped, "task", "tasks"));
+            },
+            Command::Deadline(task_id, deadline) => match self.list.reschedule(task_id, deadline) {
+                Ok(()) => match self.list.find(task_id) {
+                    Some(el) if deadline.is_some() => say!(self, "Moved the deadline of task {} ({})", task_id, el.remaining_display(self.config.day_start_hour)),
+                    _ => say!(self, "Removed the deadline of task {}", task_id)
+                },
+                Err(e) => self.fail(e)
+            },
+            Command::Shift { tag, delta } => {
+                let ids = self.list.open_with_tag(&tag);
+                let shifted = self.list.shift_deadlines(|e| ids.contains(&e.get_id()), delta);
+                say!(self, "Moved {}", utils::pluralize(shifted, "deadline", "deadlines"));
+            },
+            Command::Dedup => {
+                let pairs = self.list.duplicates();
+                for (removed, kept) in pairs.iter() {
+                    if let Some(entry) = self.list.find(*removed) {
+                        say!(self, "Task {} duplicates task {}: {}", removed, kept, entry.get_name());
+                    }
+                }
+
+                if pairs.is_empty() {
+                    say!(self, "No duplicate tasks");
+                }
+                else {
+                    say!(self, "Remove {}? (y/n)", utils::pluralize(pairs.len(), "duplicate", "duplicates"));
+                    let mut resp = String::new();
+                    read_answer(input, &mut resp);
+                    if resp.trim() == "y" {
+                        say!(self, "Removed {}", utils::pluralize(self.list.merge_duplicates(), "task", "tasks"));
+                    }
+                }
+            },
+            Command::Archive if self.read_only => self.fail("Read-only mode, tasks can't be archived"),
+            Command::Archive => {
+                let path = utils::archive_path(&self.config.file_path);
+                match utils::archive_done(&mut self.list, &path) {
+                    Ok(moved) => say!(self, "Archived {} to {}", utils::pluralize(moved, "task", "tasks"), path),
+                    Err(e) => self.fail(format!("Could not write {}: {}", path, e))
+                }
+            },
+            Command::Template(path) => match self.list.apply_template(&path) {
+                Ok(added) => say!(self, "Added {} from {}", utils::pluralize(added, "task", "tasks"), path),
+                Err(e) => self.fail(format!("Could not apply template {}: {}", path, e))
+            },
+            Command::Clean => {
+                let changed = self.list.reconcile_at(chrono::Local::now().naive_local(), self.grace());
+                say!(self, "Fixed {}", utils::pluralize(changed, "task", "tasks"));
+            },
+            Command::Focus => {
+                loop {
+                    let task_id = match utils::focus_task(&self.list) {
+                        Some(el) => {
+                            utils::show_focus(el, self.config.day_start_hour);
+                            el.get_id()
+                        },
+                        None => {
+                            say!(self, "You have no open tasks");
+                            break
+                        }
+                    };
+
+                    let mut resp = String::new();
+                    read_answer(input, &mut resp);
+                    if resp.trim() != "done" {
+                        break
+                    }
+                    if let Err(e) = self.list.close_task(task_id) {
+                        self.fail(e);
+                        break
+                    }
+                    self.completed(task_id);
+                    self.cheer();
+                }
+            },
+            Command::Menu => {
+                let ids = self.list.pending().iter().map(|e| e.get_id()).collect::<Vec<i32>>();
+                for (position, el) in self.list.pending().iter().enumerate() {
+                    say!(self, "{:>3}. {}", position + 1, el.render(&self.theme));
+                }
+
+                let mut chosen = None;
+                if ids.is_empty() {
+                    say!(self, "You have no open tasks");
+                }
+                else {
+                    say!(self, "Close which task? (1-{})", ids.len());
+                    for attempt in 0..2 {
+                        let mut resp = String::new();
+                        read_answer(input, &mut resp);
+                        chosen = utils::menu_task_id(&ids, &resp);
+                        if chosen.is_some() {
+                            break
+                        }
+                        if attempt == 0 {
+                            say!(self, "Pick a number from 1 to {}", ids.len());
+                        }
+                        else {
+                            say!(self, "Cancelled");
+                        }
+                    }
+                }
+
+                if let Some(task_id) = chosen {
+                    match self.list.close_task(task_id) {
+                        Ok(()) => {
+                            say!(self, "Closed task {}", task_id);
+                            self.closed(task_id, None);
+                            self.cheer();
+                        },
+                        Err(e) => self.fail(e)
+                    }
+                }
+            },
+            Command::Random => {
+                match self.list.random_pending() {
+                    Some(el) => say!(self, "{}", el.render(&self.theme)),
+                    None => say!(self, "You have no open tasks")
+                }
+            },
+            Command::Summary => {
+                say!(self, "{}", utils::summary_line(&self.list));
+            },
+            Command::Context => {
+                say!(self, "{}", utils::context_line(&self.config, &self.list));
+            },
+            Command::Week => {
+                let now = chrono::Local::now().naive_local();
+                let due = self.list.due_this_week(now, self.config.week_start);
+
+                if due.is_empty() {
+                    say!(self, "Nothing due this week");
+                }
+                for el in due {
+                    say!(self, "{}", el.render(&self.theme));
+                }
+            },
+            Command::Between(start, end) => {
+                let due = self.list.due_between(start, end);
+
+                if due.is_empty() {
+                    say!(self, "Nothing due from {} to {}", start, end);
+                }
+                for el in due {
+                    say!(self, "{}", el.render(&self.theme));
+                }
+            },
+            Command::Forecast(weeks) => {
+                say!(self, "{}", utils::forecast_chart(&self.list.deadline_histogram(weeks)));
+            },
+            Command::Habits => say!(self, "{}", utils::habits_chart(&self.list.created_by_weekday())),
+            Command::Debt => {
+                say!(self, "{}", utils::debt_line(&self.list));
+            },
+            Command::Show(task_id) => match self.list.find(task_id) {
+                Some(entry) => utils::show_task(entry, &self.config.date_format, self.config.day_start_hour),
+                None => self.fail(utils::TaskError::NotFound(task_id))
+            },
+            Command::SetPriority(task_id, priority) => {
+                if let Err(e) = self.list.set_priority(task_id, priority) {
+                    self.fail(e);
+                }
+            },
+            Command::Dup(task_id) => match self.list.duplicate_task(task_id) {
+                Ok(new_id) => say!(self, "Added task #{} as a copy of {}", new_id, task_id),
+                Err(e) => self.fail(e)
+            },
+            Command::Progress(task_id, pct) => match self.list.set_progress(task_id, pct) {
+                Ok(100) => {
+                    say!(self, "Task {} is at 100%, close it? (y/n)", task_id);
+                    if confirmed(input) {
+                        match self.list.close_task(task_id) {
+                            Ok(()) => {
+                                say!(self, "Closed task {}", task_id);
+                                self.closed(task_id, None);
+                                self.cheer();
+                            },
+                            Err(e) => self.fail(e)
+                        }
+                    }
+                    else {
+                        say!(self, "Task {} stays open", task_id);
+                    }
+                },
+                Ok(pct) => say!(self, "Task {} is at {}", task_id, utils::progress_bar(pct)),
+                Err(e) => self.fail(e)
+            },
+            Command::OverallProgress => say!(self, "{}", utils::overall_progress(&self.list)),
+            Command::Rename { id, name } => match self.list.rename_task(id, &name) {
+                Ok(old) => say!(self, "Renamed '{}' → '{}'", old, name),
+                Err(e) => self.fail(e)
+            },
+            Command::Remind(task_id, lead) => {
+                if let Err(e) = self.list.set_reminder(task_id, lead) {
+                    self.fail(e);
+                }
+            },
+            Command::Repeat(task_id, recurrence) => {
+                if let Err(e) = self.list.set_recurrence(task_id, recurrence) {
+                    self.fail(e);
+                }
+            },
+            Command::Comment(task_id, text) => {
+                if let Err(e) = self.list.add_comment(task_id, &text) {
+                    self.fail(e);
+                }
+            },
+            Command::Subtask { parent, name } => match self.list.add_subtask(parent, &name) {
+                Ok(task_id) => say!(self, "Added subtask {} under {}", task_id, parent),
+                Err(e) => self.fail(e)
+            },
+            Command::Tag(task_id, tags) => {
+                let tags = tags.iter().map(|t| t.as_str()).collect::<Vec<&str>>();
+                if let Err(e) = self.list.tag_task(task_id, &tags) {
+                    self.fail(e);
+                }
+            },
+            Command::Tags => utils::list_tag_counts(&self.list),
+            Command::Recent(n) => utils::show_recent(&self.list, &self.theme, n),
+            Command::Share(task_id) => match self.list.find(task_id) {
+                Some(entry) => say!(self, "{}", utils::encode_task(entry)),
+                None => self.fail(utils::TaskError::NotFound(task_id))
+            },
+            Command::Import(token) => match self.list.import_token(&token) {
+                Ok(task_id) => say!(self, "Added task #{}", task_id),
+                Err(e) => self.fail(e)
+            },
+            Command::Block(task_id, needs) => match self.list.add_dependency(task_id, needs) {
+                Ok(()) => say!(self, "Task {} now waits on {}", task_id, needs),
+                Err(e) => self.fail(e)
+            },
+            Command::Retag(old, new) => {
+                let changed = self.list.rename_tag(&old, &new);
+                say!(self, "Retagged {}", utils::pluralize(changed, "task", "tasks"));
+            },
+            Command::Link(task_id, url) => {
+                if let Err(e) = self.list.set_link(task_id, &url) {
+                    self.fail(e);
+                }
+            },
+            Command::Open(task_id) => {
+                if let Err(e) = utils::open_link(&self.list, task_id, &utils::BrowserOpener) {
+                    self.fail(e);
+                }
+            },
+            Command::Export { format, path } => match crate::export::exporter_for(&format) {
+                Some(exporter) => match std::fs::write(&path, exporter.export(&self.list)) {
+                    Ok(()) => say!(self,
+                        "Exported {} to {}", utils::pluralize(self.list.get_size(), "task", "tasks"), path
+                    ),
+                    Err(e) => self.fail(format!("Could not write {}: {}", path, e))
+                },
+                None => self.usage(format!("Unknown format '{}', use one of {}", format, crate::export::FORMATS.join(", ")))
+            },
+            Command::Remove(task_id) => match self.list.remove_task(task_id) {
+                Ok(removed) => say!(self, "Removed task {}: {}", task_id, removed.get_name()),
+                Err(e) => self.fail(e)
+            },
+            Command::Quit => return false,
+            Command::Nothing => ()
+        };
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::Cursor;
+
+    fn temp_config(name: &str) -> Config {
+        let file_path = std::env::temp_dir()
+            .join(format!("todo-{}-{}", std::process::id(), name))
+            .to_string_lossy()
+            .to_string();
+        Config { file_path, ..Config::default() }
+    }
+
+    /// Shared buffer standing in for `App::out`
+    #[derive(Clone, Default)]
+    struct Captured(std::rc::Rc<std::cell::RefCell<Vec<u8>>>);
+
+    impl Captured {
+        fn text(&self) -> String {
+            String::from_utf8(self.0.borrow().clone()).unwrap()
+        }
+    }
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn statuses_fresh_for_every_command() {
+        let past = utils::parse_deadline(String::from("2000-01-01"), 0);
+        for line in ["summary", "debt", "week", "habits", "show 0", "tags", "forecast", "help", "progress", "list --oneline"] {
+            let mut list = List::new();
+            list.add_task("Late", past).unwrap();
+            list.add_task("Later", None).unwrap();
+            assert_eq!(list.stale_overdues_at(chrono::Local::now().naive_local(), chrono::Duration::zero()), 1);
+
+            let mut app = App::new(list, temp_config("fresh.json"));
+            app.execute(line, &mut Cursor::new(""));
+            assert_eq!(
+                app.list.stale_overdues_at(chrono::Local::now().naive_local(), chrono::Duration::zero()), 0,
+                "stale status after {}", line
+            );
+            assert_eq!(*app.list.find(0).unwrap().get_status(), utils::Status::Overdue);
+        }
+    }
+
+    #[test]
+    fn piped_command_not_eaten_as_deadline() {
+        let mut app = App::new(List::new(), temp_config("piped.json"));
+        let fpath = app.config.file_path.clone();
+
+        app.run(&mut Cursor::new("add foo\nstart 0\nadd bar\n+2d\n"));
+        assert!(app.list.find(0).unwrap().get_deadline().is_none());
+        assert_eq!(app.list.find(0).unwrap().get_status(), &utils::Status::InProgress);
+        assert!(app.list.find(1).unwrap().get_deadline().is_some());
+        fs::remove_file(fpath).unwrap();
+    }
+
+    #[test]
+    fn piped_list_runs_after_add() {
+        let mut app = App::new(List::new(), temp_config("piped-list.json"));
+        let fpath = app.config.file_path.clone();
+        let out = Captured::default();
+        app.out = Box::new(out.clone());
+
+        app.run(&mut Cursor::new("add foo\nlist\n"));
+        assert!(app.list.find(0).unwrap().get_deadline().is_none());
+        assert_eq!(app.outcome, Outcome::Success);
+
+        // The plain listing goes to stdout, the oneline one shows what ran
+        app.run(&mut Cursor::new("add bar\nlist --oneline\n"));
+        assert!(out.text().contains("0\ttodo\t-\tfoo\n1\ttodo\t-\tbar\n"), "{}", out.text());
+        fs::remove_file(fpath).unwrap();
+    }
+
+    #[test]
+    fn show_missing_task_fails() {
+        let mut app = App::new(List::new(), temp_config("show-missing.json"));
+        let out = Captured::default();
+        app.out = Box::new(out.clone());
+
+        app.execute("show 9", &mut Cursor::new(""));
+        assert_eq!(app.outcome, Outcome::Failed);
+        assert_eq!(out.text(), format!("{}\n", utils::TaskError::NotFound(9)));
+    }
+
+    #[test]
+    fn tag_close_cheers_once() {
+        let yesterday = (chrono::Local::now().naive_local() - chrono::Duration::days(1)).format("%Y-%m-%dT%H:%M:%S");
+        let json = format!(
+            r#"{{"entries":[{{"id":0,"task":"Yesterday","status":"done","timestamp":"{0}","deadline":null,"completed_at":"{0}"}}],"id_tracker":1}}"#,
+            yesterday
+        );
+        let mut list: List = serde_json::from_str(&json).unwrap();
+        for name in ["Standup", "Retro", "Planning"] {
+            let id = list.add_task(name, None).unwrap();
+            list.tag_task(id, &["meeting"]).unwrap();
+        }
+        let mut app = App::new(list, temp_config("tag-cheer.json"));
+        let out = Captured::default();
+        app.out = Box::new(out.clone());
+
+        app.execute("close #meeting", &mut Cursor::new(""));
+        assert_eq!(out.text().matches("2-day streak!").count(), 1, "{}", out.text());
+        assert!(out.text().starts_with("Closed 3 tasks tagged #meeting\n"));
+    }
+
+    #[test]
+    fn full_progress_closes_on_yes_only() {
+        let mut list = List::new();
+        let done = list.add_task("Write draft", None).unwrap();
+        let waiting = list.add_task("Review draft", None).unwrap();
+        list.add_dependency(waiting, done).unwrap();
+        let mut app = App::new(list, temp_config("progress-close.json"));
+        let out = Captured::default();
+        app.out = Box::new(out.clone());
+
+        app.execute("progress 0 100", &mut Cursor::new(""));
+        assert_eq!(*app.list.find(done).unwrap().get_status(), utils::Status::Todo);
+        assert!(out.text().ends_with("Task 0 stays open\n"));
+
+        app.execute("progress 0 100", &mut Cursor::new("y\n"));
+        assert_eq!(*app.list.find(done).unwrap().get_status(), utils::Status::Done);
+        assert!(out.text().contains("Closed task 0\n"));
+        assert!(out.text().ends_with("Task 1 is no longer blocked\n"));
+    }
+
+    #[test]
+    fn unwritable_file_offers_fallback() {
+        let name = format!("todo-{}-cli-fallback.json", std::process::id());
+        let config = Config { file_path: format!("/nonexistent-dir/{}", name), ..Config::default() };
+        let fallback = utils::fallback_path(&config.file_path);
+        let mut app = App::new(List::new(), config);
+        app.list.add_task("Kept", None).unwrap();
+        let out = Captured::default();
+        app.out = Box::new(out.clone());
+
+        app.save(&mut Cursor::new("n\n"));
+        assert!(!fallback.exists());
+        assert!(out.text().ends_with("Changes were not saved\n"));
+
+        app.save(&mut Cursor::new("y\n"));
+        assert_eq!(utils::load_list(&fallback.to_string_lossy(), None).unwrap().get_size(), 1);
+        assert!(out.text().ends_with(&format!("Tasks saved to {}\n", fallback.display())));
+        fs::remove_file(fallback).unwrap();
+    }
+
+    #[test]
+    fn unreadable_file_not_overwritten() {
+        let config = temp_config("unreadable.json");
+        let fpath = config.file_path.clone();
+        fs::write(&fpath, "{not json").unwrap();
+        let mut app = App::new(List::new(), config);
+        app.list.add_task("Mine", None).unwrap();
+
+        app.save(&mut Cursor::new("n\n"));
+        assert_eq!(fs::read_to_string(&fpath).unwrap(), "{not json");
+        app.autosave();
+        assert_eq!(fs::read_to_string(&fpath).unwrap(), "{not json");
+
+        app.save(&mut Cursor::new("y\n"));
+        assert_eq!(utils::load_list(&fpath, None).unwrap().get_size(), 1);
+        fs::remove_file(fpath).unwrap();
+    }
+
+    #[test]
+    fn close_keeps_note() {
+        let mut list = List::new();
+        list.add_task("Fix the sink", None).unwrap();
+        list.add_task("Water plants", None).unwrap();
+        let mut app = App::new(list, temp_config("close-note.json"));
+
+        app.execute("close 0 : Replaced the washer", &mut Cursor::new(""));
+        app.execute("close 1", &mut Cursor::new(""));
+
+        let entry = app.list.find(0).unwrap();
+        assert_eq!(*entry.get_status(), utils::Status::Done);
+        assert_eq!(entry.get_comments().len(), 1);
+        assert_eq!(entry.get_comments()[0].1, "Replaced the washer");
+        assert!(app.list.find(1).unwrap().get_comments().is_empty());
+    }
+
+    #[test]
+    fn close_shows_summary() {
+        let mut list = List::new();
+        list.add_task("Done soon", None).unwrap();
+        list.add_task("Still open", None).unwrap();
+        let mut app = App::new(list, temp_config("close-summary.json"));
+
+        let out = Captured::default();
+        app.out = Box::new(out.clone());
+
+        app.execute("close 0", &mut Cursor::new(""));
+        assert_eq!(app.outcome, Outcome::Success);
+        assert_eq!(out.text(), "Closed task 0\n1 todo / 0 overdue\n");
+
+        out.0.borrow_mut().clear();
+        app.execute("close --quiet 1", &mut Cursor::new(""));
+        assert_eq!(app.outcome, Outcome::Success);
+        assert_eq!(out.text(), "Closed task 1\n");
+    }
+
+    #[test]
+    fn autosave_session_reads_follow_ups() {
+        let config = temp_config("autosave.json");
+        let fpath = config.file_path.clone();
+        let mut app = App::new(List::new(), config);
+
+        let mut input = LineChannel::spawn(Cursor::new(String::from("add Threaded task\n+2d\nquit\n")));
+        app.run_with_autosave(&mut input, std::time::Duration::from_millis(10));
+
+        let saved = utils::load_list(&fpath, None).unwrap();
+        assert_eq!(saved.get_size(), 1);
+        assert!(saved.get_all()[0].get_deadline().is_some());
+        fs::remove_file(fpath).unwrap();
+    }
+
+    #[test]
+    fn eof_stops_and_saves() {
+        let config = temp_config("eof.json");
+        let fpath = config.file_path.clone();
+        let mut app = App::new(List::new(), config);
+
+        // No quit, input just ends after the deadline answer
+        let mut input = Cursor::new("add Piped task\n\n");
+        app.run(&mut input);
+
+        let saved = utils::load_list(&fpath, None).unwrap();
+        assert_eq!(saved.get_size(), 1);
+        assert_eq!(saved.get_all()[0].get_name(), "Piped task");
+        fs::remove_file(fpath).unwrap();
+    }
+
+    #[test]
+    fn close_appends_audit_event() {
+        let config = Config { audit_log: true, ..temp_config("audit.json") };
+        let log = audit::audit_path(&config.file_path);
+        let _ = fs::remove_file(&log);
+
+        let mut list = List::new();
+        let task_id = list.add_task("Audited", None).unwrap();
+        let mut app = App::new(list, config);
+        app.execute(&format!("close {}", task_id), &mut Cursor::new(""));
+
+        let content = fs::read_to_string(&log).unwrap();
+        let events = content.lines()
+            .map(|l| serde_json::from_str::<audit::AuditEvent>(l).unwrap())
+            .collect::<Vec<audit::AuditEvent>>();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].op, "close");
+        assert_eq!(events[0].id, task_id);
+
+        let raw: serde_json::Value = serde_json::from_str(content.lines().next().unwrap()).unwrap();
+        assert!(raw.get("timestamp").is_some());
+        fs::remove_file(&log).unwrap();
+    }
+
+    #[test]
+    fn add_many_until_blank() {
+        let mut app = App::new(List::new(), temp_config("many.json"));
+        let mut input = Cursor::new("Buy milk\n  Call bob  \nWater plants\n\nNot a task\n");
+
+        app.execute("add-many", &mut input);
+        let names = app.list.get_all().iter().map(|e| e.get_name()).collect::<Vec<&str>>();
+        assert_eq!(names, vec!["Buy milk", "Call bob", "Water plants"]);
+        assert!(app.list.get_all().iter().all(|e| e.get_deadline().is_none()));
+    }
+
+    #[test]
+    fn outcome_exit_codes() {
+        let mut app = App::new(List::new(), temp_config("outcome.json"));
+        let mut input = Cursor::new("");
+
+        app.execute("summary", &mut input);
+        assert_eq!(app.outcome.exit_code(), 0);
+        app.execute("close 42", &mut input);
+        assert_eq!(app.outcome, Outcome::Failed);
+        assert_eq!(app.outcome.exit_code(), 1);
+        app.execute("start abc", &mut input);
+        assert_eq!(app.outcome.exit_code(), 2);
+        app.execute("frobnicate", &mut input);
+        assert_eq!(app.outcome.exit_code(), 2);
+    }
+
+    #[test]
+    fn quit_stops_reading() {
+        let config = temp_config("quit.json");
+        let fpath = config.file_path.clone();
+        let mut app = App::new(List::new(), config);
+
+        let mut input = Cursor::new("quit\nadd Never added\n\n");
+        app.run(&mut input);
+
+        assert_eq!(app.list.get_size(), 0);
+        assert_eq!(utils::load_list(&fpath, None).unwrap().get_size(), 0);
+        fs::remove_file(fpath).unwrap();
+    }
+}
@@ -0,0 +1,455 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use crate::export::{exporter_for, FORMATS};
+use crate::utils::{parse_deadline, parse_duration, parse_month, parse_shift, Priority, Recurrence, SortKey, PRIORITIES};
+use std::fmt;
+
+/// Which tasks `list` shows
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum ListFilter {
+    /// Every task, sorted by the given key or the configured default
+    All(Option<SortKey>),
+    /// Open tasks of one priority
+    Priority(Priority),
+    /// The Someday/Maybe section
+    Someday,
+    /// Tab separated, one task per line
+    Oneline
+}
+
+/// Task picked by `close`, either by id or by name
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum CloseTarget {
+    Id(i32),
+    Name(String),
+    /// Every open task with the tag
+    Tag(String)
+}
+
+/// A parsed command line
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum Command {
+    /// Deadline is asked for afterwards
+    Add { name: String },
+    /// Task names follow on their own lines
+    AddMany,
+    /// Added to Someday/Maybe, without asking for a deadline
+    Maybe { name: String },
+    Help(Option<String>),
+    List(ListFilter),
+    /// Text after a `:` is kept as a comment on the closed task, `quiet` skips the summary
+    Close { target: CloseTarget, force: bool, quiet: bool, note: Option<String> },
+    Start(i32),
+    /// Month of the report, the current one when omitted
+    Report(Option<(i32, u32)>),
+    Bump(NaiveDateTime),
+    /// `None` removes the deadline
+    Deadline(i32, Option<NaiveDateTime>),
+    /// Move the deadlines of open tasks carrying the tag
+    Shift { tag: String, delta: chrono::Duration },
+    /// Merge open tasks with the same name, after confirmation
+    Dedup,
+    /// Move closed tasks to the archive file
+    Archive,
+    /// Add the tasks of a template file
+    Template(String),
+    Clean,
+    Focus,
+    /// Pick an open task to close by its position
+    Menu,
+    Random,
+    Summary,
+    /// One line for shell prompts
+    Context,
+    Week,
+    /// Open tasks due from the first date to the second, both included
+    Between(NaiveDate, NaiveDate),
+    /// Open tasks due per week, for this many weeks
+    Forecast(usize),
+    /// Tasks created per weekday
+    Habits,
+    Debt,
+    Show(i32),
+    SetPriority(i32, Priority),
+    Dup(i32),
+    Progress(i32, i64),
+    /// Share of all tasks closed
+    OverallProgress,
+    Rename { id: i32, name: String },
+    Comment(i32, String),
+    /// `None` stops reminding
+    Remind(i32, Option<chrono::Duration>),
+    /// `None` stops the task from recurring
+    Repeat(i32, Option<Recurrence>),
+    Subtask { parent: i32, name: String },
+    Tag(i32, Vec<String>),
+    Tags,
+    /// How many tasks to show of each
+    Recent(usize),
+    Share(i32),
+    /// Add a task from a share token
+    Import(String),
+    /// First task waits until the second is closed
+    Block(i32, i32),
+    Retag(String, String),
+    Link(i32, String),
+    Open(i32),
+    Export { format: String, path: String },
+    Remove(i32),
+    Quit,
+    /// Blank line
+    Nothing
+}
+
+/// Why a command line could not be parsed
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum ParseError {
+    /// First word is not a command
+    Unknown(String),
+    /// Arguments don't fit the command, holds its usage line
+    Usage(String),
+    /// Priority word is not one of `PRIORITIES`
+    InvalidPriority(String),
+    /// Format is not one of `FORMATS`
+    InvalidFormat(String)
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Unknown(_) => write!(f, "Unknown command"),
+            ParseError::Usage(usage) => write!(f, "Usage: {}", usage),
+            ParseError::InvalidPriority(word) => write!(f, "Unknown priority '{}', use one of {}", word, PRIORITIES.join(", ")),
+            ParseError::InvalidFormat(format) => write!(f, "Unknown format '{}', use one of {}", format, FORMATS.join(", "))
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+fn usage(line: &str) -> ParseError {
+    ParseError::Usage(line.to_string())
+}
+
+fn parse_id(arg: &str, usage_line: &str) -> Result<i32, ParseError> {
+    arg.trim().parse::<i32>().map_err(|_| usage(usage_line))
+}
+
+fn parse_priority(word: &str) -> Result<Priority, ParseError> {
+    Priority::parse(word).ok_or_else(|| ParseError::InvalidPriority(word.to_string()))
+}
+
+/// Task name from command arguments, a span in single or double quotes is taken literally
+/// without its quotes. An unmatched opening quote is dropped.
+pub fn extract_quoted_name(s: &str) -> String {
+    let s = s.trim();
+    let quote = match s.chars().next() {
+        Some(c) if c == '"' || c == '\'' => c,
+        _ => return s.to_string()
+    };
+
+    let inner = &s[1..];
+    match inner.find(quote) {
+        Some(end) => inner[..end].to_string(),
+        None => inner.trim().to_string()
+    }
+}
+
+fn is_quoted(s: &str) -> bool {
+    s.starts_with('"') || s.starts_with('\'')
+}
+
+/// Split `target : note` at the first colon outside quotes, an empty note counts as none
+fn split_note(s: &str) -> (&str, Option<String>) {
+    let mut quote = None;
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (None, ':') => {
+                let note = s[i + 1..].trim();
+                return (s[..i].trim(), Some(note.to_string()).filter(|n| !n.is_empty()))
+            },
+            _ => ()
+        }
+    }
+    (s, None)
+}
+
+/// Parse a command line without running it, weekday deadlines count days from `day_start_hour`
+pub fn parse_command(line: &str, day_start_hour: u32) -> Result<Command, ParseError> {
+    let instr = line
+        .split_whitespace()
+        .next()
+        .unwrap_or("");
+    let rest = line.trim_start().strip_prefix(instr).unwrap_or("").trim();
+    let args = rest.split_whitespace().collect::<Vec<&str>>();
+
+    let command = match instr {
+        "add" => Command::Add { name: extract_quoted_name(rest) },
+        "add-many" => Command::AddMany,
+        "maybe" => Command::Maybe { name: extract_quoted_name(rest) },
+        "help" if rest.is_empty() => Command::Help(None),
+        "help" => Command::Help(Some(rest.to_string())),
+        "list" => match args.as_slice() {
+            [] => Command::List(ListFilter::All(None)),
+            ["sort", key] => match SortKey::parse(key) {
+                Some(key) => Command::List(ListFilter::All(Some(key))),
+                None => return Err(usage("list [sort priority|deadline|created|name|insertion] | list priority [low|normal|high] | list someday | list --oneline"))
+            },
+            ["priority", word] => Command::List(ListFilter::Priority(parse_priority(word)?)),
+            ["someday"] => Command::List(ListFilter::Someday),
+            ["--oneline"] => Command::List(ListFilter::Oneline),
+            _ => return Err(usage("list [sort priority|deadline|created|name|insertion] | list priority [low|normal|high] | list someday | list --oneline"))
+        },
+        "close" => {
+            let (mut force, mut quiet, mut arg) = (false, false, rest);
+            loop {
+                if let Some(tail) = arg.strip_prefix("--force") {
+                    force = true;
+                    arg = tail.trim();
+                }
+                else if let Some(tail) = arg.strip_prefix("--quiet") {
+                    quiet = true;
+                    arg = tail.trim();
+                }
+                else {
+                    break
+                }
+            }
+            let (arg, note) = split_note(arg);
+
+            // Ids take precedence, otherwise fall back to an exact name match. Quoted names are never ids.
+            let target = match arg.parse::<i32>() {
+                Ok(id) if !is_quoted(arg) => CloseTarget::Id(id),
+                _ if arg.starts_with('#') && !arg.contains(char::is_whitespace) => CloseTarget::Tag(arg[1..].to_string()),
+                _ => CloseTarget::Name(extract_quoted_name(arg))
+            };
+            Command::Close { target, force, quiet, note }
+        },
+        "start" => Command::Start(parse_id(rest, "start [task_id]")?),
+        "report" => match args.as_slice() {
+            ["month"] => Command::Report(None),
+            ["month", month] => match parse_month(month) {
+                Some(month) => Command::Report(Some(month)),
+                None => return Err(usage("report month [YYYY-MM], e.g. report month 2024-03"))
+            },
+            _ => return Err(usage("report month [YYYY-MM], e.g. report month 2024-03"))
+        },
+        "bump" => match parse_deadline(rest.to_string(), day_start_hour) {
+            Some(to) => Command::Bump(to),
+            None => return Err(usage("bump [YYYY-MM-DD|+Nd|weekday]"))
+        },
+        "deadline" => match rest.split_once(char::is_whitespace) {
+            Some((id, "none" | "clear")) => Command::Deadline(parse_id(id, "deadline [task_id] [deadline|none]")?, None),
+            Some((id, deadline)) => match parse_deadline(deadline.trim().to_string(), day_start_hour) {
+                Some(deadline) => Command::Deadline(parse_id(id, "deadline [task_id] [deadline|none]")?, Some(deadline)),
+                None => return Err(usage("deadline [task_id] [deadline|none], e.g. deadline 3 +2d"))
+            },
+            None => return Err(usage("deadline [task_id] [deadline|none], e.g. deadline 3 +2d"))
+        },
+        "shift" => match args.as_slice() {
+            [tag, delta] if tag.len() > 1 && tag.starts_with('#') => match parse_shift(delta) {
+                Some(delta) => Command::Shift { tag: tag[1..].to_string(), delta },
+                None => return Err(usage("shift [#tag] [+N|-N][m|h|d|w], e.g. shift #q3 +7d"))
+            },
+            _ => return Err(usage("shift [#tag] [+N|-N][m|h|d|w], e.g. shift #q3 +7d"))
+        },
+        "dedup" => Command::Dedup,
+        "archive" => Command::Archive,
+        "template" if rest.is_empty() => return Err(usage("template [path]")),
+        "template" => Command::Template(rest.to_string()),
+        "clean" => Command::Clean,
+        "focus" => Command::Focus,
+        "menu" => Command::Menu,
+        "random" => Command::Random,
+        "summary" => Command::Summary,
+        "context" => Command::Context,
+        "week" => Command::Week,
+        "between" => match args.as_slice() {
+            [start, end] => match (parse_deadline(start.to_string(), day_start_hour), parse_deadline(end.to_string(), day_start_hour)) {
+                (Some(start), Some(end)) if start.date() <= end.date() => Command::Between(start.date(), end.date()),
+                (Some(_), Some(_)) => return Err(usage("between [start] [end], the start can't be after the end")),
+                _ => return Err(usage("between [start] [end], e.g. between 2024-03-01 2024-03-31"))
+            },
+            _ => return Err(usage("between [start] [end], e.g. between 2024-03-01 2024-03-31"))
+        },
+        "forecast" if rest.is_empty() => Command::Forecast(4),
+        "forecast" => Command::Forecast(rest.parse::<usize>().map_err(|_| usage("forecast [weeks]"))?),
+        "habits" => Command::Habits,
+        "debt" => Command::Debt,
+        "show" => Command::Show(parse_id(rest, "show [task_id]")?),
+        "priority" => match args.as_slice() {
+            [id, word] => Command::SetPriority(parse_id(id, "priority [task_id] [low|normal|high]")?, parse_priority(word)?),
+            _ => return Err(usage("priority [task_id] [low|normal|high]"))
+        },
+        "dup" => Command::Dup(parse_id(rest, "dup [task_id]")?),
+        "progress" => match args.as_slice() {
+            [] => Command::OverallProgress,
+            [id, pct] => match (id.parse::<i32>(), pct.trim_end_matches('%').parse::<i64>()) {
+                (Ok(id), Ok(pct)) => Command::Progress(id, pct),
+                _ => return Err(usage("progress [task_id] [percent]"))
+            },
+            _ => return Err(usage("progress [task_id] [percent]"))
+        },
+        "rename" => {
+            let (id, name) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            match id.parse::<i32>() {
+                Ok(id) if !name.trim().is_empty() => Command::Rename { id, name: extract_quoted_name(name) },
+                _ => return Err(usage("rename [task_id] [task_name]"))
+            }
+        },
+        "remind" => match args.as_slice() {
+            [id, "none"] => Command::Remind(parse_id(id, "remind [task_id] [duration|none]")?, None),
+            [id, lead] => match parse_duration(lead) {
+                Some(lead) => Command::Remind(parse_id(id, "remind [task_id] [duration|none]")?, Some(lead)),
+                None => return Err(usage("remind [task_id] [duration|none], e.g. remind 4 2h"))
+            },
+            _ => return Err(usage("remind [task_id] [duration|none], e.g. remind 4 2h"))
+        },
+        "repeat" => match args.as_slice() {
+            [id, "none"] => Command::Repeat(parse_id(id, "repeat [task_id] [daily|weekly|none]")?, None),
+            [id, word] => match Recurrence::parse(word) {
+                Some(recurrence) => Command::Repeat(parse_id(id, "repeat [task_id] [daily|weekly|none]")?, Some(recurrence)),
+                None => return Err(usage("repeat [task_id] [daily|weekly|none]"))
+            },
+            _ => return Err(usage("repeat [task_id] [daily|weekly|none]"))
+        },
+        "comment" => match rest.split_once(char::is_whitespace) {
+            Some((id, text)) if !text.trim().is_empty() => Command::Comment(parse_id(id, "comment [task_id] [text]")?, text.trim().to_string()),
+            _ => return Err(usage("comment [task_id] [text]"))
+        },
+        "subtask" => {
+            let (parent, name) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            match parent.parse::<i32>() {
+                Ok(parent) if !name.trim().is_empty() => Command::Subtask { parent, name: name.trim().to_string() },
+                _ => return Err(usage("subtask [parent_id] [task_name]"))
+            }
+        },
+        "tag" => match args.split_first() {
+            Some((id, tags)) => Command::Tag(
+                parse_id(id, "tag [task_id] [tags]")?,
+                tags.iter().map(|t| t.to_string()).collect()
+            ),
+            None => return Err(usage("tag [task_id] [tags]"))
+        },
+        "tags" => Command::Tags,
+        "recent" if rest.is_empty() => Command::Recent(5),
+        "recent" => Command::Recent(rest.parse::<usize>().map_err(|_| usage("recent [n]"))?),
+        "share" => Command::Share(parse_id(rest, "share [task_id]")?),
+        "import" => match args.as_slice() {
+            ["token", token] => Command::Import(token.to_string()),
+            _ => return Err(usage("import token [token]"))
+        },
+        "block" => match args.as_slice() {
+            [id, "needs", needs] => Command::Block(
+                parse_id(id, "block [task_id] needs [other_id]")?,
+                parse_id(needs, "block [task_id] needs [other_id]")?
+            ),
+            _ => return Err(usage("block [task_id] needs [other_id]"))
+        },
+        "retag" => match args.as_slice() {
+            [old, new] => Command::Retag(old.to_string(), new.to_string()),
+            _ => return Err(usage("retag [old_tag] [new_tag]"))
+        },
+        "link" => match args.as_slice() {
+            [id, url] => Command::Link(parse_id(id, "link [task_id] [url]")?, url.to_string()),
+            _ => return Err(usage("link [task_id] [url]"))
+        },
+        "open" => Command::Open(parse_id(rest, "open [task_id]")?),
+        "export" => match args.as_slice() {
+            [format, path] if exporter_for(format).is_some() => {
+                Command::Export { format: format.to_string(), path: path.to_string() }
+            },
+            [format, _] => return Err(ParseError::InvalidFormat(format.to_string())),
+            _ => return Err(ParseError::Usage(format!("export [{}] [path]", FORMATS.join("|"))))
+        },
+        "remove" => Command::Remove(parse_id(rest, "remove [task_id]")?),
+        "quit" => Command::Quit,
+        "" => Command::Nothing,
+        other => return Err(ParseError::Unknown(other.to_string()))
+    };
+
+    Ok(command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_valid_commands() {
+        assert_eq!(parse_command("add Buy milk\n", 0), Ok(Command::Add { name: String::from("Buy milk") }));
+        assert_eq!(parse_command("close 3", 0), Ok(Command::Close { target: CloseTarget::Id(3), force: false, quiet: false, note: None }));
+        assert_eq!(parse_command("close --quiet 3", 0), Ok(Command::Close { target: CloseTarget::Id(3), force: false, quiet: true, note: None }));
+        assert_eq!(
+            parse_command("close --force Buy milk", 0),
+            Ok(Command::Close { target: CloseTarget::Name(String::from("Buy milk")), force: true, quiet: false, note: None })
+        );
+        assert_eq!(
+            parse_command("close 3 : went fine, see #12", 0),
+            Ok(Command::Close { target: CloseTarget::Id(3), force: false, quiet: false, note: Some(String::from("went fine, see #12")) })
+        );
+        assert_eq!(
+            parse_command("close 'Ratio 3:2' :", 0),
+            Ok(Command::Close { target: CloseTarget::Name(String::from("Ratio 3:2")), force: false, quiet: false, note: None })
+        );
+        assert_eq!(
+            parse_command("close #meeting", 0),
+            Ok(Command::Close { target: CloseTarget::Tag(String::from("meeting")), force: false, quiet: false, note: None })
+        );
+        assert_eq!(parse_command("list", 0), Ok(Command::List(ListFilter::All(None))));
+        assert_eq!(parse_command("list sort name", 0), Ok(Command::List(ListFilter::All(Some(SortKey::Name)))));
+        assert_eq!(parse_command("list priority high", 0), Ok(Command::List(ListFilter::Priority(Priority::High))));
+        assert_eq!(parse_command("list someday", 0), Ok(Command::List(ListFilter::Someday)));
+        assert_eq!(parse_command("list --oneline", 0), Ok(Command::List(ListFilter::Oneline)));
+        assert_eq!(parse_command("help close", 0), Ok(Command::Help(Some(String::from("close")))));
+        assert_eq!(parse_command("tag 2 #work home", 0), Ok(Command::Tag(2, vec![String::from("#work"), String::from("home")])));
+        assert_eq!(parse_command("progress 1 40%", 0), Ok(Command::Progress(1, 40)));
+        assert_eq!(parse_command("forecast", 0), Ok(Command::Forecast(4)));
+        assert_eq!(parse_command("menu", 0), Ok(Command::Menu));
+        assert_eq!(parse_command("context", 0), Ok(Command::Context));
+        assert_eq!(parse_command("deadline 3 none", 0), Ok(Command::Deadline(3, None)));
+        assert_eq!(parse_command("deadline 3 clear", 0), Ok(Command::Deadline(3, None)));
+        assert_eq!(parse_command("between 2024-03-01 2024-03-31", 0), Ok(Command::Between(
+            NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(),
+            NaiveDate::from_ymd_opt(2024, 3, 31).unwrap()
+        )));
+        assert_eq!(parse_command("shift #q3 +7d", 0), Ok(Command::Shift { tag: String::from("q3"), delta: chrono::Duration::days(7) }));
+        assert_eq!(parse_command("template templates/launch.json", 0), Ok(Command::Template(String::from("templates/launch.json"))));
+        assert_eq!(parse_command("remind 4 2h", 0), Ok(Command::Remind(4, Some(chrono::Duration::hours(2)))));
+        assert_eq!(parse_command("repeat 2 daily", 0), Ok(Command::Repeat(2, Some(Recurrence::Daily))));
+        assert_eq!(parse_command("comment 3 Waiting on Bob", 0), Ok(Command::Comment(3, String::from("Waiting on Bob"))));
+        assert_eq!(parse_command("rename 2 'Call Bob'", 0), Ok(Command::Rename { id: 2, name: String::from("Call Bob") }));
+        assert_eq!(parse_command("block 4 needs 2", 0), Ok(Command::Block(4, 2)));
+        assert_eq!(parse_command("quit\n", 0), Ok(Command::Quit));
+        assert_eq!(parse_command("   \n", 0), Ok(Command::Nothing));
+    }
+
+    #[test]
+    fn quoted_names() {
+        assert_eq!(extract_quoted_name("\"2024 planning\""), "2024 planning");
+        assert_eq!(extract_quoted_name("'call bob'"), "call bob");
+        assert_eq!(extract_quoted_name("  buy milk  "), "buy milk");
+        assert_eq!(extract_quoted_name("\"it's fine\""), "it's fine");
+        assert_eq!(extract_quoted_name("'unbalanced"), "unbalanced");
+        assert_eq!(extract_quoted_name("\""), "");
+
+        assert_eq!(parse_command("add \"42 is the answer\"", 0), Ok(Command::Add { name: String::from("42 is the answer") }));
+        assert_eq!(
+            parse_command("close '42'", 0),
+            Ok(Command::Close { target: CloseTarget::Name(String::from("42")), force: false, quiet: false, note: None })
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(parse_command("frobnicate 3", 0), Err(ParseError::Unknown(String::from("frobnicate"))));
+        assert_eq!(parse_command("start abc", 0), Err(ParseError::Usage(String::from("start [task_id]"))));
+        assert_eq!(parse_command("list sort size", 0).unwrap_err().to_string().split(' ').next(), Some("Usage:"));
+        assert_eq!(parse_command("priority 1 urgent", 0), Err(ParseError::InvalidPriority(String::from("urgent"))));
+        assert_eq!(parse_command("export pdf out.pdf", 0), Err(ParseError::InvalidFormat(String::from("pdf"))));
+        assert!(parse_command("subtask 1", 0).is_err());
+        assert!(parse_command("report month 2024-13", 0).is_err());
+        assert!(parse_command("between 2024-03-31 2024-03-01", 0).is_err());
+    }
+}
@@ -0,0 +1,318 @@
+use serde::{Serialize, Deserialize};
+use crate::utils::{SortKey, WeekMode};
+use std::{
+    env,
+    fs,
+    io::IsTerminal,
+    path::PathBuf,
+};
+
+/// Directory holding user configuration, `~/.config/todo`
+pub fn config_dir() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".config").join("todo"))
+}
+
+/// Passphrase used to encrypt the task file, from `TODO_PASSPHRASE`
+pub fn passphrase() -> Option<String> {
+    env::var("TODO_PASSPHRASE").ok().filter(|p| !p.is_empty())
+}
+
+/// Task file format from `TODO_FORMAT`, `json`, `toml` or `yaml`
+pub fn storage_format() -> Option<String> {
+    env::var("TODO_FORMAT").ok().filter(|f| !f.is_empty())
+}
+
+/// Largest task file read, in bytes, from `TODO_MAX_FILE_MB` and 8 MB by default
+pub fn max_file_bytes() -> u64 {
+    let mb = env::var("TODO_MAX_FILE_MB").ok().and_then(|m| m.parse::<u64>().ok()).filter(|m| *m > 0).unwrap_or(8);
+    mb.saturating_mul(1024 * 1024)
+}
+
+/// Valid `TODO_DAY_START_HOUR` value, an hour from 0 to 23, if any
+fn parse_day_start_hour(value: Option<&str>) -> Option<u32> {
+    value.and_then(|h| h.parse::<u32>().ok()).filter(|h| *h < 24)
+}
+
+/// Print timings to stderr, `TODO_DEBUG=1`
+pub fn debug_enabled() -> bool {
+    is_enabled(env::var("TODO_DEBUG").ok().as_deref())
+}
+
+/// Color output only on a terminal and when `NO_COLOR` is unset or empty
+pub fn color_enabled() -> bool {
+    std::io::stdout().is_terminal() && env::var("NO_COLOR").map_or(true, |v| v.is_empty())
+}
+
+/// Whether a flag value turns an option on, unset means off
+pub fn is_enabled(value: Option<&str>) -> bool {
+    matches!(value, Some("1" | "true" | "yes"))
+}
+
+/// Map a `TODO_DEFAULT_SORT` value to a sort key, invalid values sort by priority
+pub fn sort_from_config(value: Option<&str>) -> SortKey {
+    value.and_then(SortKey::parse).unwrap_or_default()
+}
+
+/// Settings read from `~/.config/todo/config.json`, each overridable by an env var
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+#[serde(default)]
+pub struct Config {
+    /// Task file, `TODO_FILE`
+    pub file_path: String,
+    /// chrono format used to print dates, `TODO_DATE_FORMAT`
+    pub date_format: String,
+    /// Leave closed tasks out of `list`, `TODO_HIDE_DONE`
+    pub hide_done: bool,
+    /// Hours past the deadline before a task counts as overdue, `TODO_GRACE_HOURS`
+    pub grace_hours: u32,
+    /// Ordering used by `list`, `TODO_DEFAULT_SORT`
+    pub default_sort: SortKey,
+    /// First day of the week for `week`, `TODO_WEEK_START`
+    pub week_start: WeekMode,
+    /// Append every added, closed and removed task to an audit log, `TODO_AUDIT_LOG`
+    pub audit_log: bool,
+    /// Warn after `add` when more tasks than this are open, `TODO_WIP_LIMIT`
+    pub wip_limit: Option<usize>,
+    /// Shell command run after a task is closed, `{task}` is replaced by its name, `TODO_ON_COMPLETE`
+    pub on_complete: Option<String>,
+    /// Refuse deadlines before the task's creation rather than warn, `TODO_STRICT_DEADLINES`
+    pub strict_deadlines: bool,
+    /// Save after this many seconds without a command in interactive sessions, `TODO_AUTOSAVE_SECS`
+    pub autosave_secs: Option<u64>,
+    /// Prompt shown before each command, only from `TODO_PROMPT`
+    #[serde(skip)]
+    pub prompt: String,
+    /// Hour, 0 to 23, at which a new day starts, only from `TODO_DAY_START_HOUR`, midnight by default
+    #[serde(skip)]
+    pub day_start_hour: u32
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            file_path: String::from("tasks.json"),
+            date_format: String::from("%Y-%m-%d %H:%M"),
+            hide_done: false,
+            grace_hours: 0,
+            default_sort: SortKey::Priority,
+            week_start: WeekMode::Monday,
+            audit_log: false,
+            wip_limit: None,
+            on_complete: None,
+            strict_deadlines: false,
+            autosave_secs: None,
+            prompt: String::from("todo> "),
+            day_start_hour: 0
+        }
+    }
+}
+
+impl Config {
+    /// Parse a config from JSON, missing fields keep their default
+    pub fn from_json(content: &str) -> Option<Self> {
+        serde_json::from_str(content).ok()
+    }
+
+    /// Read config from file, falling back to the defaults if absent or invalid
+    pub fn from_file(fpath: &str) -> Self {
+        match fs::read_to_string(fpath) {
+            Ok(content) => Config::from_json(&content).unwrap_or_else(|| {
+                println!("Invalid config file {}, using defaults", fpath);
+                Config::default()
+            }),
+            Err(_) => Config::default()
+        }
+    }
+
+    /// Override fields with the values found by `lookup`, ignoring invalid ones
+    pub fn apply_env<F: Fn(&str) -> Option<String>>(&mut self, lookup: F) {
+        if let Some(file_path) = lookup("TODO_FILE") {
+            self.file_path = file_path;
+        }
+        if let Some(date_format) = lookup("TODO_DATE_FORMAT") {
+            self.date_format = date_format;
+        }
+        if let Some(hide_done) = lookup("TODO_HIDE_DONE") {
+            self.hide_done = matches!(hide_done.as_str(), "1" | "true" | "yes");
+        }
+        if let Some(grace_hours) = lookup("TODO_GRACE_HOURS").and_then(|h| h.parse::<u32>().ok()) {
+            self.grace_hours = grace_hours;
+        }
+        if let Some(default_sort) = lookup("TODO_DEFAULT_SORT") {
+            self.default_sort = sort_from_config(Some(&default_sort));
+        }
+        if let Some(audit_log) = lookup("TODO_AUDIT_LOG") {
+            self.audit_log = matches!(audit_log.as_str(), "1" | "true" | "yes");
+        }
+        if let Some(wip_limit) = lookup("TODO_WIP_LIMIT").and_then(|l| l.parse::<usize>().ok()) {
+            self.wip_limit = Some(wip_limit);
+        }
+        if let Some(on_complete) = lookup("TODO_ON_COMPLETE").filter(|c| !c.trim().is_empty()) {
+            self.on_complete = Some(on_complete);
+        }
+        if let Some(strict_deadlines) = lookup("TODO_STRICT_DEADLINES") {
+            self.strict_deadlines = is_enabled(Some(&strict_deadlines));
+        }
+        if let Some(autosave_secs) = lookup("TODO_AUTOSAVE_SECS").and_then(|s| s.parse::<u64>().ok()).filter(|s| *s > 0) {
+            self.autosave_secs = Some(autosave_secs);
+        }
+        if let Some(week_start) = lookup("TODO_WEEK_START").and_then(|w| WeekMode::parse(&w)) {
+            self.week_start = week_start;
+        }
+        if let Some(prompt) = lookup("TODO_PROMPT") {
+            self.prompt = prompt;
+        }
+        if let Some(hour) = parse_day_start_hour(lookup("TODO_DAY_START_HOUR").as_deref()) {
+            self.day_start_hour = hour;
+        }
+    }
+
+    /// Load config from `~/.config/todo/config.json` and the environment
+    pub fn load() -> Self {
+        let mut config = match config_dir() {
+            Some(dir) => Config::from_file(&dir.join("config.json").to_string_lossy()),
+            None => Config::default()
+        };
+        config.apply_env(|key| env::var(key).ok());
+        config
+    }
+}
+
+/// Markers printed in front of each task, per status
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+#[serde(default)]
+pub struct Theme {
+    pub overdue: String,
+    pub todo: String,
+    pub done: String,
+    pub in_progress: String,
+    /// Printed before the streak count after closing a task
+    pub streak: String
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            overdue: String::from("*"),
+            todo: String::from("|"),
+            done: String::from("-"),
+            in_progress: String::from(">"),
+            streak: String::from("🔥")
+        }
+    }
+}
+
+impl Theme {
+    /// Parse a theme from JSON, missing markers keep their default
+    pub fn from_json(content: &str) -> Option<Self> {
+        serde_json::from_str(content).ok()
+    }
+
+    /// Read theme from file, falling back to the default markers if absent or invalid
+    pub fn from_file(fpath: &str) -> Self {
+        match fs::read_to_string(fpath) {
+            Ok(content) => Theme::from_json(&content).unwrap_or_else(|| {
+                println!("Invalid theme file {}, using default markers", fpath);
+                Theme::default()
+            }),
+            Err(_) => Theme::default()
+        }
+    }
+
+    /// Load theme from `~/.config/todo/theme.json`
+    pub fn load() -> Self {
+        match config_dir() {
+            Some(dir) => Theme::from_file(&dir.join("theme.json").to_string_lossy()),
+            None => Theme::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prompt_env() {
+        let mut config = Config::default();
+        config.apply_env(|key| (key == "TODO_PROMPT").then(|| String::from("tasks$ ")));
+        assert_eq!(config.prompt, "tasks$ ");
+
+        let mut config = Config::default();
+        config.apply_env(|_| None);
+        assert_eq!(config.prompt, "todo> ");
+    }
+
+    #[test]
+    fn day_start_hour_env() {
+        let mut config = Config::default();
+        config.apply_env(|key| (key == "TODO_DAY_START_HOUR").then(|| String::from("4")));
+        assert_eq!(config.day_start_hour, 4);
+
+        config.apply_env(|key| (key == "TODO_DAY_START_HOUR").then(|| String::from("24")));
+        assert_eq!(config.day_start_hour, 4);
+    }
+
+    #[test]
+    fn flags_opt_in() {
+        assert!(is_enabled(Some("1")));
+        assert!(is_enabled(Some("yes")));
+        assert!(!is_enabled(Some("0")));
+        assert!(!is_enabled(None));
+    }
+
+    #[test]
+    fn default_sort_config() {
+        assert_eq!(sort_from_config(Some("deadline")), SortKey::Deadline);
+        assert_eq!(sort_from_config(Some("Name")), SortKey::Name);
+        assert_eq!(sort_from_config(Some("insertion")), SortKey::Insertion);
+        assert_eq!(sort_from_config(Some("priority-ish")), SortKey::Priority);
+        assert_eq!(sort_from_config(None), SortKey::Priority);
+    }
+
+    #[test]
+    fn config_partial() {
+        let config = Config::from_json(r#"{"hide_done": true, "default_sort": "deadline"}"#).unwrap();
+
+        assert!(config.hide_done);
+        assert_eq!(config.default_sort, SortKey::Deadline);
+        assert_eq!(config.file_path, "tasks.json");
+        assert_eq!(config.date_format, "%Y-%m-%d %H:%M");
+        assert_eq!(config.grace_hours, 0);
+        assert!(Config::from_json("{not json").is_none());
+    }
+
+    #[test]
+    fn config_env_override() {
+        let mut config = Config::default();
+        config.apply_env(|key| match key {
+            "TODO_FILE" => Some(String::from("work.json")),
+            "TODO_GRACE_HOURS" => Some(String::from("abc")),
+            "TODO_DEFAULT_SORT" => Some(String::from("name")),
+            "TODO_WEEK_START" => Some(String::from("rolling")),
+            "TODO_WIP_LIMIT" => Some(String::from("5")),
+            _ => None
+        });
+
+        assert_eq!(config.file_path, "work.json");
+        assert_eq!(config.grace_hours, 0);
+        assert_eq!(config.default_sort, SortKey::Name);
+        assert_eq!(config.week_start, WeekMode::Rolling);
+        assert_eq!(config.wip_limit, Some(5));
+    }
+
+    #[test]
+    fn theme_partial() {
+        let theme = Theme::from_json(r#"{"overdue": "!!"}"#).unwrap();
+
+        assert_eq!(theme.overdue, "!!");
+        assert_eq!(theme.todo, "|");
+        assert_eq!(theme.done, "-");
+    }
+
+    #[test]
+    fn theme_missing_file() {
+        let theme = Theme::from_file("does/not/exist/theme.json");
+        assert_eq!(theme, Theme::default());
+    }
+}
@@ -0,0 +1,249 @@
+use crate::utils::{Entry, List, Status};
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+/// Converts a task list to a text format
+pub trait Exporter {
+    fn export(&self, list: &List) -> String;
+}
+
+/// Same JSON layout as the task file
+pub struct JsonExporter;
+
+/// One row per task, with a header row
+pub struct CsvExporter;
+
+/// A checklist, one item per task
+pub struct MarkdownExporter;
+
+/// Names accepted by `exporter_for`
+pub const FORMATS: &[&str] = &["json", "csv", "markdown"];
+
+/// Find the exporter for a format name
+pub fn exporter_for(format: &str) -> Option<Box<dyn Exporter>> {
+    match format.to_lowercase().as_str() {
+        "json" => Some(Box::new(JsonExporter)),
+        "csv" => Some(Box::new(CsvExporter)),
+        "markdown" | "md" => Some(Box::new(MarkdownExporter)),
+        _ => None
+    }
+}
+
+fn status_name(status: &Status) -> &'static str {
+    match status {
+        Status::Done => "done",
+        Status::Todo => "todo",
+        Status::Overdue => "overdue",
+        Status::InProgress => "in progress"
+    }
+}
+
+/// Quote a CSV field if it contains a separator, quote or line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    }
+    else {
+        field.to_string()
+    }
+}
+
+impl Exporter for JsonExporter {
+    fn export(&self, list: &List) -> String {
+        serde_json::to_string(list).unwrap()
+    }
+}
+
+impl Exporter for CsvExporter {
+    fn export(&self, list: &List) -> String {
+        let mut out = String::from("id,status,name,created,deadline,tags\n");
+        for el in list.get_all().iter() {
+            let deadline = el.get_deadline().map(|d| d.to_string()).unwrap_or_default();
+            out.push_str(&format!(
+                "{},{},{},{},{},{}\n",
+                el.get_id(),
+                status_name(el.get_status()),
+                csv_field(el.get_name()),
+                el.get_timestamp(),
+                deadline,
+                csv_field(&el.get_tags().join(" "))
+            ));
+        }
+        out
+    }
+}
+
+fn markdown_item(el: &Entry) -> String {
+    let check = if *el.get_status() == Status::Done { "x" } else { " " };
+    let mut line = format!("- [{}] {}", check, el.get_name());
+    if let Some(d) = el.get_deadline() {
+        line.push_str(&format!(" (due {})", d.format("%Y-%m-%d")));
+    }
+    for tag in el.get_tags().iter() {
+        line.push_str(&format!(" #{}", tag));
+    }
+    line
+}
+
+impl Exporter for MarkdownExporter {
+    fn export(&self, list: &List) -> String {
+        let mut out = String::from("# Tasks\n\n");
+        for el in list.get_all().iter() {
+            out.push_str(&markdown_item(el));
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// Serialization used for the task file, chosen with `TODO_FORMAT` or the file extension
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Default)]
+pub enum StorageFormat {
+    #[default]
+    Json,
+    Toml,
+    Yaml
+}
+
+impl StorageFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "json" => Some(StorageFormat::Json),
+            "toml" => Some(StorageFormat::Toml),
+            "yaml" | "yml" => Some(StorageFormat::Yaml),
+            _ => None
+        }
+    }
+
+    /// Use the configured format if valid, otherwise the one matching the extension of `fpath`
+    pub fn resolve(configured: Option<&str>, fpath: &str) -> Self {
+        configured.and_then(StorageFormat::parse)
+            .or_else(|| Path::new(fpath).extension().and_then(|e| e.to_str()).and_then(StorageFormat::parse))
+            .unwrap_or_default()
+    }
+
+    pub fn serialize(&self, list: &List) -> Result<Vec<u8>, Error> {
+        match self {
+            StorageFormat::Json => Ok(JsonExporter.export(list).into_bytes()),
+            #[cfg(feature = "toml")]
+            StorageFormat::Toml => toml::to_string(list)
+                .map(String::into_bytes)
+                .map_err(|e| Error::new(ErrorKind::InvalidData, e)),
+            #[cfg(feature = "yaml")]
+            StorageFormat::Yaml => serde_yaml::to_string(list)
+                .map(String::into_bytes)
+                .map_err(|e| Error::new(ErrorKind::InvalidData, e)),
+            #[allow(unreachable_patterns)]
+            _ => Err(self.unsupported())
+        }
+    }
+
+    pub fn deserialize(&self, data: &[u8]) -> Result<List, Error> {
+        match self {
+            StorageFormat::Json => serde_json::from_slice(data).map_err(|e| Error::new(ErrorKind::InvalidData, e)),
+            #[cfg(feature = "toml")]
+            StorageFormat::Toml => std::str::from_utf8(data)
+                .map_err(|e| Error::new(ErrorKind::InvalidData, e))
+                .and_then(|content| toml::from_str(content).map_err(|e| Error::new(ErrorKind::InvalidData, e))),
+            #[cfg(feature = "yaml")]
+            StorageFormat::Yaml => serde_yaml::from_slice(data).map_err(|e| Error::new(ErrorKind::InvalidData, e)),
+            #[allow(unreachable_patterns)]
+            _ => Err(self.unsupported())
+        }
+    }
+
+    /// Formats whose feature wasn't enabled at build time
+    #[allow(dead_code)]
+    fn unsupported(&self) -> Error {
+        Error::new(ErrorKind::Unsupported, format!("Built without {:?} support, enable the matching feature", self))
+    }
+}
+
+/// Tasks as CSV
+pub fn export_csv(list: &List) -> String {
+    CsvExporter.export(list)
+}
+
+/// Tasks as a Markdown checklist
+pub fn export_markdown(list: &List) -> String {
+    MarkdownExporter.export(list)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::parse_deadline;
+
+    fn sample() -> List {
+        let mut list = List::new();
+        list.add_task("Plain", None).unwrap();
+        list.add_task("Has, comma", parse_deadline(String::from("2030-01-02"), 0)).unwrap();
+        list.tag_task(1, &["work"]).unwrap();
+        list.close_task(0).unwrap();
+        list
+    }
+
+    #[test]
+    fn storage_format_resolve() {
+        assert_eq!(StorageFormat::resolve(None, "tasks.json"), StorageFormat::Json);
+        assert_eq!(StorageFormat::resolve(None, "tasks.yml"), StorageFormat::Yaml);
+        assert_eq!(StorageFormat::resolve(None, "tasks"), StorageFormat::Json);
+        assert_eq!(StorageFormat::resolve(Some("TOML"), "tasks.json"), StorageFormat::Toml);
+        assert_eq!(StorageFormat::resolve(Some("xml"), "tasks.toml"), StorageFormat::Toml);
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let list = sample();
+        let data = StorageFormat::Json.serialize(&list).unwrap();
+        let restored = StorageFormat::Json.deserialize(&data).unwrap();
+        assert_eq!(JsonExporter.export(&restored), JsonExporter.export(&list));
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn toml_round_trip() {
+        let list = sample();
+        let data = StorageFormat::Toml.serialize(&list).unwrap();
+        let restored = StorageFormat::Toml.deserialize(&data).unwrap();
+        assert_eq!(JsonExporter.export(&restored), JsonExporter.export(&list));
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn yaml_round_trip() {
+        let list = sample();
+        let data = StorageFormat::Yaml.serialize(&list).unwrap();
+        let restored = StorageFormat::Yaml.deserialize(&data).unwrap();
+        assert_eq!(JsonExporter.export(&restored), JsonExporter.export(&list));
+    }
+
+    #[test]
+    fn exporters_non_empty() {
+        let list = sample();
+        for format in FORMATS.iter() {
+            let out = exporter_for(format).unwrap().export(&list);
+            assert!(!out.is_empty(), "{} export is empty", format);
+        }
+        assert!(exporter_for("ical").is_none());
+    }
+
+    #[test]
+    fn csv_quoting() {
+        let out = export_csv(&sample());
+        let rows = out.lines().collect::<Vec<&str>>();
+
+        assert_eq!(rows.len(), 3);
+        assert!(rows[1].starts_with("0,done,Plain,"));
+        assert!(rows[2].starts_with("1,todo,\"Has, comma\","));
+        assert!(rows[2].ends_with(",2030-01-02 00:00:00,work"));
+    }
+
+    #[test]
+    fn markdown_items() {
+        let out = export_markdown(&sample());
+
+        assert!(out.contains("- [x] Plain\n"));
+        assert!(out.contains("- [ ] Has, comma (due 2030-01-02) #work\n"));
+    }
+}
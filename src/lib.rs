@@ -1 +1,7 @@
+pub mod audit;
+pub mod cli;
+pub mod command;
+pub mod config;
+pub mod crypto;
+pub mod export;
 pub mod utils;
\ No newline at end of file
@@ -1,53 +1,76 @@
+pub mod audit;
+pub mod cli;
+pub mod command;
+pub mod config;
+pub mod crypto;
+pub mod export;
 pub mod utils;
-use std::io;
-
-const FILENAME: &str = "tasks.json";
+use std::io::{self, IsTerminal};
+use std::time::Instant;
 
 fn main() {
-    let mut all_tasks = utils::read_or_create(FILENAME);
-    let mut input = String::new();
+    let config = config::Config::load();
+    let filename = config.file_path.as_str();
 
-    loop {
-        io::stdin().read_line(&mut input).expect("Error reading input");
+    let mut read_only = false;
+    if let Err(e) = utils::acquire_lock(filename) {
+        println!("Could not lock {} ({}), another instance may be running", filename, e);
+        // Piped input holds commands, not an answer
+        if !io::stdin().is_terminal() {
+            println!("Not opening {} without a terminal to confirm read-only mode", filename);
+            std::process::exit(1);
+        }
+        println!("Open in read-only mode? (y/n)");
 
-        let instr =  input
-            .split_whitespace()
-            .next()
-            .unwrap_or("");
-        
-        match instr {
-            "add" => {
-                let task_name = input
-                    .strip_prefix(instr).unwrap_or("")
-                    .trim_start();
-            
-                println!("Add deadline? (format: YYYY-MM-DD)");
-                let mut deadline_resp = String::new();
-                io::stdin().read_line(&mut deadline_resp).expect("Error reading input");
-                
-                let deadline = utils::parse_deadline(deadline_resp);
+        // A read error counts as no
+        let mut resp = String::new();
+        if io::stdin().read_line(&mut resp).is_err() || resp.trim() != "y" {
+            return
+        }
+        read_only = true;
+    }
 
-                all_tasks.add_task(task_name, deadline);
-            },
-            "help" => utils::show_help(),
-            "list" => {
-                all_tasks.check_overdues();
-                utils::list_tasks(&all_tasks)
-            },
-            "close" => {
-                let task_id = input
-                    .strip_prefix(instr).unwrap_or("")
-                    .trim_start().trim().parse::<i32>().unwrap();
-                all_tasks.close_task(task_id).unwrap();
-            },
-            "remove" => println!("TODO: Remove task"),
-            "quit" => break,
-            "" => (),
-            _ => println!("Unknown command")
-        };
+    let started = Instant::now();
+    let list = match utils::read_or_create(filename) {
+        Ok(list) => list,
+        Err(e) => {
+            println!("Could not open {}: {}", filename, e);
+            if !read_only {
+                let _ = utils::release_lock(filename);
+            }
+            std::process::exit(1);
+        }
+    };
+    let mut app = cli::App::new(list, config.clone());
+    app.debug = config::debug_enabled();
+    app.debug_timing("load", started);
+    app.theme = config::Theme::load();
+    app.interactive = io::stdin().is_terminal();
+    app.read_only = read_only;
 
-        input.clear();
+    // Arguments run as a single command, e.g. `todo close 3`
+    let args = std::env::args().skip(1).collect::<Vec<String>>();
+    let code = if args.is_empty() {
+        match config.autosave_secs {
+            Some(secs) => {
+                let mut input = cli::LineChannel::spawn(io::BufReader::new(io::stdin()));
+                app.run_with_autosave(&mut input, std::time::Duration::from_secs(secs));
+            },
+            None => app.run(&mut io::stdin().lock())
+        }
+        0
     }
+    else {
+        app.interactive = false;
+        app.timed_execute(&args.join(" "), &mut io::stdin().lock());
+        app.timed_save(&mut io::stdin().lock());
+        app.outcome.exit_code()
+    };
 
-    utils::export(all_tasks, FILENAME);
+    if !read_only {
+        if let Err(e) = utils::release_lock(filename) {
+            println!("Could not remove {}: {}", utils::lock_path(filename), e);
+        }
+    }
+    std::process::exit(code);
 }
@@ -1,26 +1,283 @@
 use chrono::{
-    prelude::{NaiveDateTime, Local},
+    prelude::{NaiveDate, NaiveDateTime, Local},
+    Datelike,
+    Days,
+    Duration,
+    LocalResult,
+    NaiveTime,
     TimeZone,
+    Weekday,
 };
 use serde::{Serialize, Deserialize};
 use std::collections::HashMap;
+use unicode_width::UnicodeWidthStr;
+use crate::config::{max_file_bytes, passphrase, storage_format, Config, Theme};
+use crate::crypto;
+use crate::export::StorageFormat;
 use std::{
     fs,
     fmt,
-    io::{Error, ErrorKind, BufReader},
-    path::Path,
+    io::{Read, Write},
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+    process::Command,
 };
 
+/// Stored lowercase, the capitalized names of older files are still read
 #[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Hash, Clone)]
+#[serde(rename_all = "lowercase")]
 pub enum Status {
     /// Closed tasks
+    #[serde(alias = "Done")]
     Done,
     /// Ongoing tasks
+    #[serde(alias = "Todo")]
     Todo,
     /// Tasks past deadline
-    Overdue
+    #[serde(alias = "Overdue")]
+    Overdue,
+    /// Tasks being worked on
+    #[serde(alias = "InProgress")]
+    InProgress
 }
 
+impl Status {
+    /// Name as stored in the task file
+    pub fn name(&self) -> &'static str {
+        match self {
+            Status::Done => "done",
+            Status::Todo => "todo",
+            Status::Overdue => "overdue",
+            Status::InProgress => "inprogress"
+        }
+    }
+}
+
+/// Ordering applied to each status group when listing
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    /// High priority first, then earliest deadline, then oldest
+    #[default]
+    Priority,
+    /// Order tasks were added in
+    Insertion,
+    /// Earliest deadline first, tasks without one last
+    Deadline,
+    /// Oldest first
+    Created,
+    /// Alphabetical, ignoring case
+    Name
+}
+
+impl SortKey {
+    /// Lowercase name, as accepted by `parse`
+    pub fn name(&self) -> &'static str {
+        match self {
+            SortKey::Priority => "priority",
+            SortKey::Insertion => "insertion",
+            SortKey::Deadline => "deadline",
+            SortKey::Created => "created",
+            SortKey::Name => "name"
+        }
+    }
+
+    pub fn parse(key: &str) -> Option<Self> {
+        match key.trim().to_lowercase().as_str() {
+            "priority" => Some(SortKey::Priority),
+            "insertion" | "id" => Some(SortKey::Insertion),
+            "deadline" => Some(SortKey::Deadline),
+            "created" => Some(SortKey::Created),
+            "name" => Some(SortKey::Name),
+            _ => None
+        }
+    }
+
+    /// Sort tasks in place, ties keep insertion order
+    pub fn sort(&self, entries: &mut [Entry]) {
+        match self {
+            SortKey::Priority => entries.sort_by_key(default_order_key),
+            SortKey::Insertion => entries.sort_by_key(|e| e.id),
+            SortKey::Deadline => entries.sort_by_key(|e| (e.deadline.is_none(), e.deadline)),
+            SortKey::Created => entries.sort_by_key(|e| e.timestamp),
+            SortKey::Name => entries.sort_by_key(|e| e.task.to_lowercase())
+        }
+    }
+}
+
+/// How urgent a task is
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Hash, Clone, Copy, Default, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    #[serde(alias = "Low")]
+    Low,
+    #[default]
+    #[serde(alias = "Normal")]
+    Normal,
+    #[serde(alias = "High")]
+    High
+}
+
+/// Priority names accepted by `Priority::parse`
+pub const PRIORITIES: [&str; 3] = ["low", "normal", "high"];
+
+impl Priority {
+    pub fn parse(word: &str) -> Option<Self> {
+        match word.trim().to_lowercase().as_str() {
+            "low" => Some(Priority::Low),
+            "normal" => Some(Priority::Normal),
+            "high" => Some(Priority::High),
+            _ => None
+        }
+    }
+}
+
+/// What closed a task, for telling single closes from bulk ones.
+/// Missed occurrences of recurring tasks move the deadline instead of closing, see `Entry::nudge_at`.
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum CloseReason {
+    /// A single task closed with `close` or from `focus` and `menu`
+    Manual,
+    /// One of several tasks closed at once, e.g. `close #tag`
+    Bulk
+}
+
+/// How often a task comes back
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Recurrence {
+    Daily,
+    Weekly
+}
+
+impl Recurrence {
+    pub fn parse(word: &str) -> Option<Self> {
+        match word.trim().to_lowercase().as_str() {
+            "daily" => Some(Recurrence::Daily),
+            "weekly" => Some(Recurrence::Weekly),
+            _ => None
+        }
+    }
+
+    /// Time between two occurrences
+    pub fn interval(&self) -> Duration {
+        match self {
+            Recurrence::Daily => Duration::days(1),
+            Recurrence::Weekly => Duration::weeks(1)
+        }
+    }
+}
+
+/// How the current week is delimited
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum WeekMode {
+    /// Monday to Sunday
+    #[default]
+    Monday,
+    /// Sunday to Saturday
+    Sunday,
+    /// Today and the following six days
+    Rolling
+}
+
+impl WeekMode {
+    pub fn parse(mode: &str) -> Option<Self> {
+        match mode.trim().to_lowercase().as_str() {
+            "monday" => Some(WeekMode::Monday),
+            "sunday" => Some(WeekMode::Sunday),
+            "rolling" => Some(WeekMode::Rolling),
+            _ => None
+        }
+    }
+}
+
+/// Day that `now` belongs to when days start at `day_start_hour`, e.g. 2am is still yesterday with 4
+pub fn logical_today(now: NaiveDateTime, day_start_hour: u32) -> NaiveDate {
+    (now - Duration::hours(day_start_hour.min(23) as i64)).date()
+}
+
+/// First and last day, inclusive, of the week containing `now`, days starting at `day_start_hour`
+pub fn week_bounds(now: NaiveDateTime, mode: WeekMode, day_start_hour: u32) -> (NaiveDate, NaiveDate) {
+    let today = logical_today(now, day_start_hour);
+    let start = match mode {
+        WeekMode::Monday => today - Days::new(today.weekday().num_days_from_monday() as u64),
+        WeekMode::Sunday => today - Days::new(today.weekday().num_days_from_sunday() as u64),
+        WeekMode::Rolling => today
+    };
+    (start, start + Days::new(6))
+}
+
+/// Key of `SortKey::Priority` and `List::default_order`
+fn default_order_key(e: &Entry) -> (std::cmp::Reverse<Priority>, bool, Option<NaiveDateTime>, NaiveDateTime) {
+    (std::cmp::Reverse(e.priority), e.deadline.is_none(), e.deadline, e.timestamp)
+}
+
+/// Errors returned by task list operations
+#[derive(Debug, Eq, PartialEq)]
+pub enum TaskError {
+    /// Task name is empty
+    EmptyName,
+    /// No open task has the given id
+    NotFound(i32),
+    /// No open task has the given name
+    NameNotFound(String),
+    /// Several open tasks share the given name
+    Ambiguous(String, usize),
+    /// Link is not an http(s) URL
+    InvalidLink(String),
+    /// Task has no link attached
+    NoLink(i32),
+    /// Task is already started or closed
+    NotStartable(i32),
+    /// Another task already has the given id
+    DuplicateId(i32),
+    /// Id leaves no room for a next one
+    IdOverflow(i32),
+    /// Making the first task wait on the second would make them wait on each other, or on itself when both are the same
+    DependencyCycle(i32, i32),
+    /// Share token could not be decoded
+    InvalidToken,
+    /// Deadline is earlier than the moment the task was added
+    DeadlineBeforeCreation,
+    /// Deadline offset goes past the dates that can be stored
+    DeadlineOutOfRange,
+    /// Relative deadline isn't written as `+Nd` or `+Nw`
+    InvalidOffset(String),
+    /// Relative deadline is longer than the given number of days
+    OffsetTooLong(i64),
+    /// Id was never issued, with the lowest existing id and the next id to be issued
+    OutOfRange { id: i32, min: i32, cursor: i32 },
+}
+
+impl fmt::Display for TaskError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TaskError::EmptyName => write!(f, "Cannot add empty task name"),
+            TaskError::NotFound(id) => write!(f, "Open task with id {} not found", id),
+            TaskError::NameNotFound(name) => write!(f, "Open task named '{}' not found", name),
+            TaskError::Ambiguous(name, count) => write!(f, "{} open tasks are named '{}', use an id instead", count, name),
+            TaskError::InvalidLink(link) => write!(f, "'{}' is not an http(s) link", link),
+            TaskError::NoLink(id) => write!(f, "Task {} has no link", id),
+            TaskError::NotStartable(id) => write!(f, "Task {} is already in progress or closed", id),
+            TaskError::DuplicateId(id) => write!(f, "A task with id {} already exists", id),
+            TaskError::IdOverflow(id) => write!(f, "Task id {} is too large", id),
+            TaskError::DependencyCycle(id, needs) if id == needs => write!(f, "A task can't depend on itself"),
+            TaskError::DependencyCycle(id, needs) => write!(f, "Task {} already depends on {}", needs, id),
+            TaskError::InvalidToken => write!(f, "Not a valid task token"),
+            TaskError::DeadlineBeforeCreation => write!(f, "Deadline is before the task was created"),
+            TaskError::DeadlineOutOfRange => write!(f, "Deadline is too far away"),
+            TaskError::InvalidOffset(offset) => write!(f, "'{}' is not a valid offset, use +Nd or +Nw", offset),
+            TaskError::OffsetTooLong(days) => write!(f, "Relative deadlines are limited to {} days", days),
+            TaskError::OutOfRange { id, min, cursor } if min >= cursor => write!(f, "No task #{} (no tasks yet)", id),
+            TaskError::OutOfRange { id, min, cursor } => write!(f, "No task #{} (valid range {}..{})", id, min, cursor),
+        }
+    }
+}
+
+impl std::error::Error for TaskError {}
+
 /// Represents a single task
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Entry {
@@ -32,17 +289,74 @@ pub struct Entry {
     status: Status,
     /// Timestamp of creation
     timestamp: NaiveDateTime,
-    deadline: Option<NaiveDateTime>
+    deadline: Option<NaiveDateTime>,
+    /// Free-form labels, stored without a leading '#'
+    #[serde(default)]
+    tags: Vec<String>,
+    /// Related ticket or document
+    #[serde(default)]
+    link: Option<String>,
+    /// Id of the task this one is a subtask of
+    #[serde(default)]
+    parent: Option<i32>,
+    /// Timestamp of closing
+    #[serde(default)]
+    completed_at: Option<NaiveDateTime>,
+    /// Percentage of the task done, 0 to 100
+    #[serde(default)]
+    progress: Option<u8>,
+    /// Deadline is a whole day rather than an exact time, inferred from a midnight deadline when absent
+    #[serde(default)]
+    all_day: Option<bool>,
+    #[serde(default)]
+    priority: Priority,
+    /// Deadline given relative to creation, e.g. `+2w`, kept so copies get their own deadline
+    #[serde(default, with = "offset_seconds")]
+    deadline_offset: Option<Duration>,
+    /// Ids of the tasks that must be closed before this one can start
+    #[serde(default)]
+    blocked_by: Vec<i32>,
+    /// Timestamped notes, oldest first
+    #[serde(default)]
+    comments: Vec<(NaiveDateTime, String)>,
+    #[serde(default)]
+    recurrence: Option<Recurrence>,
+    /// How long before the deadline to start reminding, e.g. `2h`
+    #[serde(default, with = "offset_seconds")]
+    remind_before: Option<Duration>,
+    /// Occurrences of a recurring task that passed without it being closed
+    #[serde(default)]
+    skipped: u32,
+    /// How the task was closed, absent for tasks closed before it was kept
+    #[serde(default)]
+    completed_via: Option<CloseReason>
+}
+
+/// Stores an optional `Duration` as whole seconds
+mod offset_seconds {
+    use chrono::Duration;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(offset: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error> {
+        match offset {
+            Some(d) => serializer.serialize_some(&d.num_seconds()),
+            None => serializer.serialize_none()
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+        Ok(Option::<i64>::deserialize(deserializer)?.and_then(Duration::try_seconds))
+    }
+}
+
+/// Deadlines given as a bare date are stored at midnight
+fn is_date_only(deadline: Option<NaiveDateTime>) -> bool {
+    deadline.is_some_and(|d| d.time() == NaiveTime::MIN)
 }
 
 impl fmt::Debug for Entry {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let marker = match self.status {
-            Status::Done => "-",
-            Status::Overdue => "*",
-            Status::Todo => "|"
-        };
-        write!(f, "{} {} {}", marker, self.id, self.task)
+        write!(f, "{}", self.render(&Theme::default()))
     }
 }
 
@@ -53,32 +367,233 @@ impl Entry {
             task: name,
             status: Status::Todo,
             timestamp: Local::now().naive_local(),
-            deadline
+            deadline,
+            tags: Vec::new(),
+            link: None,
+            parent: None,
+            completed_at: None,
+            progress: None,
+            all_day: Some(is_date_only(deadline)),
+            priority: Priority::Normal,
+            deadline_offset: None,
+            blocked_by: Vec::new(),
+            comments: Vec::new(),
+            recurrence: None,
+            remind_before: None,
+            skipped: 0,
+            completed_via: None
         }
     }
 
-    /// Check if task is past deadline based on current time
-    pub fn is_overdue(&self) -> bool {
-        let curr_time = Local::now().naive_local();
+    pub fn get_id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn get_name(&self) -> &str {
+        self.task.trim_end()
+    }
+
+    pub fn get_status(&self) -> &Status {
+        &self.status
+    }
+
+    pub fn get_timestamp(&self) -> NaiveDateTime {
+        self.timestamp
+    }
+
+    pub fn get_deadline(&self) -> Option<NaiveDateTime> {
+        self.deadline
+    }
+
+    pub fn set_deadline(&mut self, deadline: Option<NaiveDateTime>) {
+        self.deadline = deadline;
+        self.all_day = Some(is_date_only(deadline));
+    }
+
+    /// Low priority Todo task without a deadline, kept out of the main list
+    pub fn is_someday(&self) -> bool {
+        self.status == Status::Todo && self.deadline.is_none() && self.priority == Priority::Low
+    }
+
+    /// Check that the task isn't due before it was created, a whole day deadline counts to the end of the day
+    pub fn deadline_is_sane(&self, day_start_hour: u32) -> bool {
+        !matches!(self.due_at_with(day_start_hour), Some(due) if due < self.timestamp)
+    }
+
+    pub fn is_all_day(&self) -> bool {
+        self.all_day.unwrap_or_else(|| is_date_only(self.deadline))
+    }
+
+    pub fn set_all_day(&mut self, all_day: bool) {
+        self.all_day = Some(all_day);
+    }
+
+    /// Moment the task becomes late, for all-day tasks the end of the day with days starting at `day_start_hour`
+    pub fn due_at_with(&self, day_start_hour: u32) -> Option<NaiveDateTime> {
         match self.deadline {
-            Some(d) => d < curr_time,
+            Some(d) if self.is_all_day() => Some(d.date().and_time(NaiveTime::MIN) + Days::new(1) + Duration::hours(day_start_hour.min(23) as i64)),
+            d => d
+        }
+    }
+
+    pub fn get_parent(&self) -> Option<i32> {
+        self.parent
+    }
+
+    pub fn get_completed_at(&self) -> Option<NaiveDateTime> {
+        self.completed_at
+    }
+
+    pub fn get_progress(&self) -> Option<u8> {
+        self.progress
+    }
+
+    pub fn get_deadline_offset(&self) -> Option<Duration> {
+        self.deadline_offset
+    }
+
+    pub fn get_blocked_by(&self) -> &Vec<i32> {
+        &self.blocked_by
+    }
+
+    pub fn get_comments(&self) -> &Vec<(NaiveDateTime, String)> {
+        &self.comments
+    }
+
+    pub fn get_remind_before(&self) -> Option<Duration> {
+        self.remind_before
+    }
+
+    /// Check whether `now` falls between the start of the reminder and the deadline
+    pub fn should_remind_at(&self, now: NaiveDateTime, day_start_hour: u32) -> bool {
+        match (self.due_at_with(day_start_hour), self.remind_before) {
+            // A lead reaching before the earliest representable date reminds right away
+            (Some(due), Some(lead)) if self.status != Status::Done => due.checked_sub_signed(lead).is_none_or(|start| start <= now) && now < due,
+            _ => false
+        }
+    }
+
+    pub fn get_completed_via(&self) -> Option<CloseReason> {
+        self.completed_via
+    }
+
+    pub fn get_recurrence(&self) -> Option<Recurrence> {
+        self.recurrence
+    }
+
+    pub fn get_skipped(&self) -> u32 {
+        self.skipped
+    }
+
+    /// Move the deadline of an open recurring task past `now` one occurrence at a time,
+    /// leaving it Todo. Returns how many occurrences were skipped.
+    pub fn nudge_at(&mut self, now: NaiveDateTime, grace: Duration, day_start_hour: u32) -> u32 {
+        let interval = match self.recurrence {
+            Some(r) if matches!(self.status, Status::Todo | Status::Overdue) => r.interval(),
+            _ => return 0
+        };
+
+        let mut skipped = 0;
+        while self.is_overdue_at(now, grace, day_start_hour) {
+            match self.deadline.and_then(|d| d.checked_add_signed(interval)) {
+                Some(next) => self.deadline = Some(next),
+                None => break
+            }
+            skipped += 1;
+        }
+        if skipped > 0 {
+            self.status = Status::Todo;
+            self.skipped += skipped;
+        }
+        skipped
+    }
+
+    pub fn get_priority(&self) -> Priority {
+        self.priority
+    }
+
+    pub fn get_tags(&self) -> &Vec<String> {
+        &self.tags
+    }
+
+    /// Format task as a single line using the markers of the given theme
+    pub fn render(&self, theme: &Theme) -> String {
+        let marker = match self.status {
+            Status::Done => &theme.done,
+            Status::Overdue => &theme.overdue,
+            Status::Todo => &theme.todo,
+            Status::InProgress => &theme.in_progress
+        };
+        let mut line = format!("{} {} {}", marker, self.id, self.task.trim_end());
+        for tag in self.tags.iter() {
+            line.push_str(&format!(" #{}", tag));
+        }
+        line
+    }
+
+    /// Check if task is past deadline based on current time, see `due_at_with`
+    pub fn is_overdue(&self, day_start_hour: u32) -> bool {
+        self.is_overdue_after(Duration::zero(), day_start_hour)
+    }
+
+    /// Check if task is past deadline by more than `grace`
+    pub fn is_overdue_after(&self, grace: Duration, day_start_hour: u32) -> bool {
+        self.is_overdue_at(Local::now().naive_local(), grace, day_start_hour)
+    }
+
+    /// Check if task is past deadline by more than `grace` at `now`
+    pub fn is_overdue_at(&self, now: NaiveDateTime, grace: Duration, day_start_hour: u32) -> bool {
+        match self.due_at_with(day_start_hour) {
+            Some(d) => d + grace < now,
             _ => false
         }
     }
 
+    /// Time left until the deadline, zero once it has passed
+    pub fn time_remaining(&self, day_start_hour: u32) -> Option<Duration> {
+        let curr_time = Local::now().naive_local();
+        self.due_at_with(day_start_hour).map(|d| (d - curr_time).max(Duration::zero()))
+    }
+
+    /// Human readable time until, or since, the deadline
+    pub fn remaining_display(&self, day_start_hour: u32) -> String {
+        match self.due_at_with(day_start_hour) {
+            Some(d) if self.is_overdue(day_start_hour) => humanize_remaining(Some(Local::now().naive_local() - d), true),
+            _ => humanize_remaining(self.time_remaining(day_start_hour), false)
+        }
+    }
+
     /// Get number of days since the task has been created
     pub fn delta() {
         unimplemented!()
     }
 }
 
+/// One task of a template file, see `List::apply_template`
+#[derive(Deserialize)]
+struct TemplateTask {
+    name: String,
+    #[serde(default)]
+    deadline_offset_days: Option<i64>,
+    #[serde(default)]
+    priority: Priority,
+    #[serde(default)]
+    tags: Vec<String>
+}
+
 /// Task list
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct List {
     /// Vector containing all tasks
-    pub entries: Vec<Entry>,
+    entries: Vec<Entry>,
     /// Current id cursor
-    id_tracker: i32
+    id_tracker: i32,
+    /// When the list was last saved, the file's mtime for files written before it was kept
+    #[serde(default)]
+    last_modified: Option<NaiveDateTime>,
+    /// Hour, 0 to 23, at which a new day starts, see `Config::day_start_hour`
+    #[serde(skip)]
+    day_start_hour: u32
 }
 
 impl Default for List {
@@ -90,7 +605,20 @@ impl Default for List {
 impl List {
     /// Constructor
     pub fn new() -> Self {
-        Self { entries: Vec::new(), id_tracker: 0 }
+        Self { entries: Vec::new(), id_tracker: 0, last_modified: None, day_start_hour: 0 }
+    }
+
+    /// Use `hour` as the start of each day when the list decides what is due or overdue
+    pub fn set_day_start_hour(&mut self, hour: u32) {
+        self.day_start_hour = hour;
+    }
+
+    pub fn get_day_start_hour(&self) -> u32 {
+        self.day_start_hour
+    }
+
+    pub fn get_last_modified(&self) -> Option<NaiveDateTime> {
+        self.last_modified
     }
 
     /// Return total tasks
@@ -103,240 +631,4141 @@ impl List {
         &self.entries
     }
 
-    pub fn get_cursor(&self) -> i32 {
-        self.id_tracker
+    /// Check that an id lies between the lowest existing id and the cursor, for a clearer error than not found
+    pub fn check_id(&self, id: i32) -> Result<(), TaskError> {
+        let cursor = self.get_cursor();
+        let min = self.entries.iter().map(|e| e.id).min().unwrap_or(cursor);
+        if id < 0 || id < min || id >= cursor {
+            return Err(TaskError::OutOfRange { id, min, cursor })
+        }
+        Ok(())
     }
 
-    pub fn inc_cursor(&mut self) {
-        self.id_tracker += 1;
+    /// All tasks, high priority first, then by earliest deadline, then oldest first
+    pub fn default_order(&self) -> Vec<&Entry> {
+        let mut ordered = self.entries.iter().collect::<Vec<&Entry>>();
+        ordered.sort_by_key(|e| default_order_key(e));
+        ordered
     }
 
-    pub fn add_task(&mut self, task: &str, deadline: Option<NaiveDateTime>){
-        if task.is_empty() { 
-            println!("Cannot add empty task name");
-        }
-    
-        let new_task = Entry::new(self.get_cursor(), task.to_string(), deadline);
-        self.entries.push(new_task);
-        self.inc_cursor();
+    /// Copies of all tasks in their current order, to keep without borrowing the list
+    pub fn snapshot(&self) -> Vec<Entry> {
+        self.entries.clone()
     }
 
-    pub fn close_task(&mut self, id: i32) -> Result<(), Error> {
-        for i in 0..self.get_size() {
-            if self.entries[i].id == id && self.entries[i].status != Status::Done{
-                self.entries[i].status = Status::Done;
-                return Ok(())
-            }
-        }
-        Err(Error::new(ErrorKind::InvalidInput, format!("Open task with id {} not found", id)))
+    /// Return task with the given id
+    pub fn find(&self, id: i32) -> Option<&Entry> {
+        self.entries.iter().find(|e| e.id == id)
     }
 
-    /// Obtain count of tasks by status
-    pub fn get_status(&self) -> HashMap<Status, u8> {
-        let mut counts = HashMap::from([
-            (Status::Todo, 0),
-            (Status::Done, 0),
-            (Status::Overdue, 0)
-        ]);
-
-        for el in self.get_all().iter() {
-            let val = counts.get(&el.status).unwrap();
-            counts.insert(el.status.clone(), val + 1);
-        }
-
-        counts
+    /// Return mutable access to the task with the given id
+    pub fn find_mut(&mut self, id: i32) -> Option<&mut Entry> {
+        self.entries.iter_mut().find(|e| e.id == id)
     }
 
-    pub fn check_overdues(&mut self) {
-        for i in 0..self.get_size() {
-            if self.entries[i].is_overdue() {
-                self.entries[i].status = Status::Overdue;
-            }
-        }
+    /// Return all open tasks, i.e. everything not Done
+    pub fn pending(&self) -> Vec<&Entry> {
+        self.entries.iter().filter(|e| e.status != Status::Done).collect()
     }
-}
 
-/// Open JSON file
-pub fn open_file(fpath: &str) -> List {
-    let content = fs::File::open(fpath).unwrap();
-    let reader = BufReader::new(content);
-
-    serde_json::from_reader(reader).unwrap()
-}
-
-/// Reads JSON file or creates a new task list if there is no file
-pub fn read_or_create(fpath: &str) -> List{
-    if Path::new(fpath).exists() {
-        open_file(fpath)
+    /// Tasks closed during the given month
+    pub fn completed_in_month(&self, year: i32, month: u32) -> Vec<&Entry> {
+        self.entries.iter()
+            .filter(|e| e.completed_at.is_some_and(|d| d.year() == year && d.month() == month))
+            .collect()
     }
-    else {
-        List::new()
+
+    /// Tasks added during the given month
+    pub fn created_in_month(&self, year: i32, month: u32) -> Vec<&Entry> {
+        self.entries.iter()
+            .filter(|e| e.timestamp.year() == year && e.timestamp.month() == month)
+            .collect()
     }
-}
 
-/// Save task list to JSON file
-pub fn export(list: List, fpath: &str) {
-    let f = serde_json::to_string(&list).unwrap();
+    /// The `n` most recently added tasks, newest first
+    pub fn recent_created(&self, n: usize) -> Vec<&Entry> {
+        let mut recent = self.entries.iter().collect::<Vec<&Entry>>();
+        recent.sort_by_key(|e| std::cmp::Reverse((e.timestamp, e.id)));
+        recent.truncate(n);
+        recent
+    }
 
-    fs::write(fpath, f).expect("Error writing file");
-}
+    /// The `n` most recently closed tasks, newest first
+    pub fn recent_closed(&self, n: usize) -> Vec<&Entry> {
+        let mut recent = self.entries.iter()
+            .filter(|e| e.status == Status::Done && e.completed_at.is_some())
+            .collect::<Vec<&Entry>>();
+        recent.sort_by_key(|e| std::cmp::Reverse((e.completed_at, e.id)));
+        recent.truncate(n);
+        recent
+    }
 
-pub fn parse_deadline(mut deadline_raw: String) -> Option<NaiveDateTime> {
-    if deadline_raw.ends_with('\n') {
-        deadline_raw.pop();
+    /// Open task with the earliest deadline
+    pub fn next_deadline(&self) -> Option<&Entry> {
+        self.pending().into_iter()
+            .filter(|e| e.deadline.is_some())
+            .min_by_key(|e| (e.deadline, e.id))
     }
 
-    let parts = deadline_raw.split('-').collect::<Vec<&str>>();
+    /// Pick an open task at random
+    pub fn random_pending(&self) -> Option<&Entry> {
+        self.random_pending_with(&mut XorShift::from_time())
+    }
 
-    if parts.len() != 3 {
-        return None
+    /// Pick an open task using the given random source
+    pub fn random_pending_with(&self, rng: &mut dyn Rng) -> Option<&Entry> {
+        let pending = self.pending();
+        if pending.is_empty() {
+            return None
+        }
+        Some(pending[(rng.next_u64() % pending.len() as u64) as usize])
     }
 
-    let year = parts[0].to_string().parse::<i32>().ok();
-    let month = parts[1].to_string().parse::<u32>().ok();
-    let day = parts[2].to_string().parse::<u32>().ok();
+    /// Open tasks with a deadline in the week containing `now`, earliest first
+    pub fn due_this_week(&self, now: NaiveDateTime, mode: WeekMode) -> Vec<&Entry> {
+        let (start, end) = week_bounds(now, mode, self.day_start_hour);
+        self.due_between(start, end)
+    }
 
-    let new_local = match (year, month, day) {
-        (Some(year), Some(month), Some(day)) => Local.with_ymd_and_hms(year, month, day, 0, 0, 0),
-        _ => return None
-    };
+    /// Open tasks due from `start` to `end` included, soonest first
+    pub fn due_between(&self, start: NaiveDate, end: NaiveDate) -> Vec<&Entry> {
+        let mut due = self.pending().into_iter()
+            .filter(|e| matches!(e.deadline, Some(d) if d.date() >= start && d.date() <= end))
+            .collect::<Vec<&Entry>>();
+        due.sort_by_key(|e| e.deadline);
+        due
+    }
 
-    new_local.single().map(|t| t.naive_local())
-}
+    /// Open tasks due in each of the next `weeks` ISO weeks, keyed by the monday starting the week
+    pub fn deadline_histogram(&self, weeks: usize) -> Vec<(NaiveDate, usize)> {
+        self.deadline_histogram_at(Local::now().naive_local(), weeks)
+    }
 
-pub fn list_tasks(list: &List) {
-    let mut overdues: Vec<Entry> = Vec::new();
-    let mut todos: Vec<Entry> = Vec::new();
-    let mut dones: Vec<Entry> = Vec::new();
+    /// Same as `deadline_histogram`, the first week is the one containing `now`.
+    /// Overdue tasks and tasks without a deadline are left out.
+    pub fn deadline_histogram_at(&self, now: NaiveDateTime, weeks: usize) -> Vec<(NaiveDate, usize)> {
+        let (start, _) = week_bounds(now, WeekMode::Monday, self.day_start_hour);
+        let mut buckets = (0..weeks)
+            .map(|i| (start + Days::new(7 * i as u64), 0))
+            .collect::<Vec<(NaiveDate, usize)>>();
 
-    for i in 0..list.entries.len() {
-        match list.entries[i].status {
-            Status::Done => dones.push(list.entries[i].clone()),
-            Status::Overdue => overdues.push(list.entries[i].clone()),
-            Status::Todo => todos.push(list.entries[i].clone())
+        for entry in self.pending() {
+            let deadline = match entry.deadline {
+                Some(d) if entry.status != Status::Overdue && entry.due_at_with(self.day_start_hour).is_some_and(|due| due >= now) => d,
+                _ => continue
+            };
+            let week = (deadline.date() - start).num_days() as usize / 7;
+            if let Some(bucket) = buckets.get_mut(week) {
+                bucket.1 += 1;
+            }
         }
+        buckets
     }
 
-    if overdues.is_empty() {
-        println!("You have no overdue tasks");
-    }
-    else {
-        for el in overdues { print!("{:?}", el) }
+    /// Tasks with the given status, in insertion order
+    pub fn with_status(&self, status: Status) -> impl Iterator<Item = &Entry> {
+        self.entries.iter().filter(move |e| e.status == status)
     }
 
-    if todos.is_empty() {
-        println!("You have no tasks")
-    }
-    else {
-        for el in todos { print!("{:?}", el) }
+    /// Mutable access to each task, without adding or removing any
+    pub fn entries_mut(&mut self) -> impl Iterator<Item = &mut Entry> {
+        self.entries.iter_mut()
     }
 
-    if !dones.is_empty() { 
-        for el in dones {print!("{:?}", el) }
+    pub fn get_cursor(&self) -> i32 {
+        self.id_tracker
     }
-}
-
-pub fn show_help() {
-    let help_string = "
-    Usage:
-    add [task_name]
-        Adds new task named [task_name] under TODO.
-    
-    list
-        List all overdue, todo and closed tasks, in that order.
-    
-    close [task_id]
-        Close task with provided [task_id], moves it from TODO to done.
-    
-    remove [task_id]
-        Removes task from list. Other task ids are not affected.
-
-    quit
-        Exit TODO cli.
-    ";
-    println!("{}", help_string);
-}
 
-#[cfg(test)]
-mod tests {
-    use std::time::Duration;
+    pub fn inc_cursor(&mut self) {
+        self.id_tracker += 1;
+    }
 
-    use chrono::Datelike;
+    /// Add a new Todo task and return its id
+    pub fn add_task(&mut self, task: &str, deadline: Option<NaiveDateTime>) -> Result<i32, TaskError> {
+        self.add_task_checked(task, deadline, false)
+    }
 
-    use super::*;
-    use crate::utils::List;
+    /// Add a new Todo task, when `strict` refusing a deadline before its creation, see `Entry::deadline_is_sane`
+    pub fn add_task_checked(&mut self, task: &str, deadline: Option<NaiveDateTime>, strict: bool) -> Result<i32, TaskError> {
+        if task.trim().is_empty() {
+            return Err(TaskError::EmptyName)
+        }
 
-    #[test]
-    fn list_size() {
-        let mut list = List::new();
-        let total_tasks = 100;
-        for i in 0..total_tasks {
-            let curr_task_name = format!("Sample task {}", i);
-            list.add_task(&curr_task_name, None);
+        let id = self.get_cursor();
+        let new_task = Entry::new(id, task.to_string(), deadline);
+        if strict && !new_task.deadline_is_sane(self.day_start_hour) {
+            return Err(TaskError::DeadlineBeforeCreation)
         }
-        assert_eq!(list.get_size(), total_tasks);
+        self.entries.push(new_task);
+        self.inc_cursor();
+        Ok(id)
     }
 
-    #[test]
-    fn new_task() {
-        let mut list = List::new();
-        list.add_task("Sample task", None);
-
-        let to_close: i32 = 0;
+    /// Add a new Todo task due `offset` after its creation and return its id
+    pub fn add_task_in(&mut self, task: &str, offset: Duration) -> Result<i32, TaskError> {
+        if task.trim().is_empty() {
+            return Err(TaskError::EmptyName)
+        }
 
-        assert_eq!(list.entries[to_close as usize].status, Status::Todo);
-        list.close_task(to_close).unwrap();
-        assert_eq!(list.entries[to_close as usize].status, Status::Done);
+        let mut entry = Entry::new(self.get_cursor(), task.to_string(), None);
+        let deadline = entry.timestamp.checked_add_signed(offset).ok_or(TaskError::DeadlineOutOfRange)?;
+        entry.set_deadline(Some(deadline));
+        entry.deadline_offset = Some(offset);
+        self.push_entry(entry)
     }
 
-    #[test]
-    fn parse_pass() {
-        let line = String::from("2024-01-01\n");
-        let res = parse_deadline(line).unwrap();
-        let exp = Local.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap().naive_local();
+    /// Insert a pre-built task as is, moving the cursor past its id
+    pub fn push_entry(&mut self, entry: Entry) -> Result<i32, TaskError> {
+        let id = entry.id;
+        if self.find(id).is_some() {
+            return Err(TaskError::DuplicateId(id))
+        }
+        let next = id.checked_add(1).ok_or(TaskError::IdOverflow(id))?;
 
-        assert_eq!(res, exp);
+        self.entries.push(entry);
+        self.id_tracker = self.id_tracker.max(next);
+        Ok(id)
     }
 
-    #[test]
-    fn parse_fail_not_date() {
-        let line_error = String::from("2024-01-011232");
-        let res = parse_deadline(line_error);
-        assert!(res.is_none());
+    /// Add a task from a share token under the next free id
+    pub fn import_token(&mut self, token: &str) -> Result<i32, TaskError> {
+        let mut entry = decode_task(token)?;
+        entry.id = self.get_cursor();
+        self.push_entry(entry)
     }
 
-    #[test]
-    fn parse_fail_empty() {
-        let line_error = String::from("");
-        let res = parse_deadline(line_error);
-        assert!(res.is_none());
+    /// Add a low priority task without deadline, see `someday`, and return its id
+    pub fn add_someday(&mut self, task: &str) -> Result<i32, TaskError> {
+        let id = self.add_task(task, None)?;
+        self.set_priority(id, Priority::Low)?;
+        Ok(id)
+    }
+
+    /// Add the tasks of a JSON template file, deadlines are `deadline_offset_days` after now.
+    /// Nothing is added unless the whole template is valid, returns how many tasks were added.
+    pub fn apply_template(&mut self, template_path: &str) -> Result<usize, Error> {
+        let tasks: Vec<TemplateTask> = serde_json::from_str(&fs::read_to_string(template_path)?)?;
+        let invalid = |e: TaskError| Error::new(ErrorKind::InvalidData, e.to_string());
+        if tasks.iter().any(|t| t.name.trim().is_empty()) {
+            return Err(invalid(TaskError::EmptyName))
+        }
+
+        let now = Local::now().naive_local();
+        let mut offsets = Vec::with_capacity(tasks.len());
+        for task in tasks.iter() {
+            let offset = match task.deadline_offset_days {
+                Some(days) => Some(Duration::try_days(days)
+                    .filter(|offset| now.checked_add_signed(*offset).is_some())
+                    .ok_or_else(|| invalid(TaskError::DeadlineOutOfRange))?),
+                None => None
+            };
+            offsets.push(offset);
+        }
+
+        for (task, offset) in tasks.iter().zip(offsets) {
+            let id = match offset {
+                Some(offset) => self.add_task_in(&task.name, offset),
+                None => self.add_task(&task.name, None)
+            };
+            let id = id.map_err(invalid)?;
+            let tags = task.tags.iter().map(|t| t.as_str()).collect::<Vec<&str>>();
+            let _ = self.set_priority(id, task.priority);
+            let _ = self.tag_task(id, &tags);
+        }
+        Ok(tasks.len())
+    }
+
+    /// Add a task under an existing one and return the new task's id
+    pub fn add_subtask(&mut self, parent: i32, task: &str) -> Result<i32, TaskError> {
+        if self.find(parent).is_none() {
+            return Err(TaskError::NotFound(parent))
+        }
+
+        let id = self.add_task(task, None)?;
+        if let Some(entry) = self.find_mut(id) {
+            entry.parent = Some(parent);
+        }
+        Ok(id)
+    }
+
+    pub fn close_task(&mut self, id: i32) -> Result<(), TaskError> {
+        self.close_task_via(id, CloseReason::Manual)
+    }
+
+    /// Close a task, recording what closed it
+    pub fn close_task_via(&mut self, id: i32, reason: CloseReason) -> Result<(), TaskError> {
+        match self.find_mut(id) {
+            Some(entry) if entry.status != Status::Done => {
+                entry.status = Status::Done;
+                entry.completed_at = Some(Local::now().naive_local());
+                entry.completed_via = Some(reason);
+                Ok(())
+            },
+            _ => Err(TaskError::NotFound(id))
+        }
+    }
+
+    /// Add a new Todo task copying the name, deadline and tags of another, returning the new id.
+    /// A deadline given relative to creation is counted again from the copy's creation.
+    pub fn duplicate_task(&mut self, id: i32) -> Result<i32, TaskError> {
+        let source = match self.find(id) {
+            Some(entry) => entry.clone(),
+            None => return Err(TaskError::NotFound(id))
+        };
+
+        let new_id = match source.deadline_offset {
+            Some(offset) => self.add_task_in(&source.task, offset)?,
+            None => self.add_task(&source.task, source.deadline)?
+        };
+        if let Some(entry) = self.find_mut(new_id) {
+            entry.tags = source.tags;
+            entry.priority = source.priority;
+        }
+        Ok(new_id)
+    }
+
+    /// Move a Todo or Overdue task to InProgress
+    pub fn start_task(&mut self, id: i32) -> Result<(), TaskError> {
+        match self.find_mut(id) {
+            Some(entry) if entry.status == Status::Todo || entry.status == Status::Overdue => {
+                entry.status = Status::InProgress;
+                Ok(())
+            },
+            Some(_) => Err(TaskError::NotStartable(id)),
+            None => Err(TaskError::NotFound(id))
+        }
+    }
+
+    /// Close a task, treating an already closed task as success
+    pub fn close_task_idempotent(&mut self, id: i32) -> Result<(), TaskError> {
+        if self.find(id).is_some_and(|e| e.status == Status::Done) {
+            return Ok(())
+        }
+        self.close_task(id)
+    }
+
+    /// Close the only open task whose name matches, ignoring case, and return its id
+    pub fn close_by_name(&mut self, name: &str) -> Result<i32, TaskError> {
+        let name = name.trim();
+        let matches = self.pending().into_iter()
+            .filter(|e| e.task.trim().eq_ignore_ascii_case(name))
+            .map(|e| e.id)
+            .collect::<Vec<i32>>();
+
+        match matches.len() {
+            0 => Err(TaskError::NameNotFound(name.to_string())),
+            1 => {
+                self.close_task(matches[0])?;
+                Ok(matches[0])
+            },
+            n => Err(TaskError::Ambiguous(name.to_string(), n))
+        }
+    }
+
+    /// Add tags to a task, ignoring the ones it already has
+    pub fn tag_task(&mut self, id: i32, tags: &[&str]) -> Result<(), TaskError> {
+        let entry = match self.find_mut(id) {
+            Some(entry) => entry,
+            None => return Err(TaskError::NotFound(id))
+        };
+
+        for tag in tags.iter() {
+            let tag = tag.trim_start_matches('#');
+            if !tag.is_empty() && !entry.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+                entry.tags.push(tag.to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// Ids of open tasks carrying a tag, ignoring case and a leading '#'
+    pub fn open_with_tag(&self, tag: &str) -> Vec<i32> {
+        let tag = tag.trim().trim_start_matches('#');
+        self.pending().into_iter()
+            .filter(|e| e.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)))
+            .map(|e| e.id)
+            .collect()
+    }
+
+    /// Close every open task carrying a tag, returning how many were closed
+    pub fn close_by_tag(&mut self, tag: &str) -> usize {
+        let ids = self.open_with_tag(tag);
+        for id in ids.iter() {
+            let _ = self.close_task_via(*id, CloseReason::Bulk);
+        }
+        ids.len()
+    }
+
+    /// Make a task wait until another one is closed
+    pub fn add_dependency(&mut self, id: i32, needs: i32) -> Result<(), TaskError> {
+        for task_id in [id, needs] {
+            if self.find(task_id).is_none() {
+                return Err(TaskError::NotFound(task_id))
+            }
+        }
+        if id == needs || self.depends_on(needs, id) {
+            return Err(TaskError::DependencyCycle(id, needs))
+        }
+
+        let entry = self.find_mut(id).unwrap();
+        if !entry.blocked_by.contains(&needs) {
+            entry.blocked_by.push(needs);
+        }
+        Ok(())
+    }
+
+    /// Check whether a task waits on another, directly or through other tasks
+    pub fn depends_on(&self, id: i32, other: i32) -> bool {
+        let mut stack = vec![id];
+        let mut seen = Vec::new();
+
+        while let Some(current) = stack.pop() {
+            if seen.contains(&current) {
+                continue
+            }
+            seen.push(current);
+
+            if let Some(entry) = self.find(current) {
+                if entry.blocked_by.contains(&other) {
+                    return true
+                }
+                stack.extend(entry.blocked_by.iter().copied());
+            }
+        }
+        false
+    }
+
+    /// Check whether any task the given one waits on is still open
+    pub fn is_blocked(&self, entry: &Entry) -> bool {
+        entry.blocked_by.iter()
+            .any(|id| self.find(*id).is_some_and(|e| e.status != Status::Done))
+    }
+
+    /// Ids of open tasks still waiting on another
+    pub fn blocked_ids(&self) -> Vec<i32> {
+        self.pending().into_iter()
+            .filter(|e| self.is_blocked(e))
+            .map(|e| e.id)
+            .collect()
+    }
+
+    /// Open tasks that waited on `closed` and no longer wait on anything
+    pub fn unblocked_by(&self, closed: i32) -> Vec<i32> {
+        self.pending().into_iter()
+            .filter(|e| e.blocked_by.contains(&closed) && !self.is_blocked(e))
+            .map(|e| e.id)
+            .collect()
+    }
+
+    /// Change how urgent a task is
+    pub fn set_priority(&mut self, id: i32, priority: Priority) -> Result<(), TaskError> {
+        match self.find_mut(id) {
+            Some(entry) => {
+                entry.priority = priority;
+                Ok(())
+            },
+            None => Err(TaskError::NotFound(id))
+        }
+    }
+
+    /// Move or clear a task's deadline, an open task becomes Todo or Overdue to match
+    pub fn reschedule(&mut self, id: i32, deadline: Option<NaiveDateTime>) -> Result<(), TaskError> {
+        let day_start_hour = self.day_start_hour;
+        let entry = self.find_mut(id).ok_or(TaskError::NotFound(id))?;
+        entry.set_deadline(deadline);
+        entry.deadline_offset = None;
+        if matches!(entry.status, Status::Todo | Status::Overdue) {
+            entry.status = if entry.is_overdue(day_start_hour) { Status::Overdue } else { Status::Todo };
+        }
+        Ok(())
+    }
+
+    /// Tasks created on each weekday, Monday first
+    pub fn created_by_weekday(&self) -> [usize; 7] {
+        let mut counts = [0; 7];
+        for entry in self.entries.iter() {
+            counts[entry.timestamp.weekday().num_days_from_monday() as usize] += 1;
+        }
+        counts
+    }
+
+    /// Consecutive days, ending today, with at least one task closed
+    pub fn current_streak(&self) -> usize {
+        self.current_streak_at(logical_today(Local::now().naive_local(), self.day_start_hour))
+    }
+
+    /// Consecutive days ending on `today` with at least one task closed, 0 if none was closed that day
+    pub fn current_streak_at(&self, today: NaiveDate) -> usize {
+        let days = self.entries.iter()
+            .filter_map(|e| e.completed_at.map(|d| logical_today(d, self.day_start_hour)))
+            .collect::<std::collections::HashSet<NaiveDate>>();
+
+        let mut streak = 0;
+        let mut day = Some(today);
+        while let Some(d) = day.filter(|d| days.contains(d)) {
+            streak += 1;
+            day = d.pred_opt();
+        }
+        streak
+    }
+
+    /// Someday/maybe ideas: Todo tasks with no deadline and low priority
+    pub fn someday(&self) -> Vec<&Entry> {
+        self.entries.iter().filter(|e| e.is_someday()).collect()
+    }
+
+    /// Open tasks of the given priority, earliest deadline first
+    pub fn filter_by_priority(&self, priority: Priority) -> Vec<&Entry> {
+        let mut matching = self.pending().into_iter()
+            .filter(|e| e.priority == priority)
+            .collect::<Vec<&Entry>>();
+        matching.sort_by_key(|e| (e.deadline.is_none(), e.deadline));
+        matching
+    }
+
+    /// Give a task a new name and return the one it had
+    pub fn rename_task(&mut self, id: i32, name: &str) -> Result<String, TaskError> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(TaskError::EmptyName)
+        }
+
+        match self.find_mut(id) {
+            Some(entry) => Ok(std::mem::replace(&mut entry.task, name.to_string()).trim_end().to_string()),
+            None => Err(TaskError::NotFound(id))
+        }
+    }
+
+    /// Append a note to a task, stamped with the current time
+    pub fn add_comment(&mut self, id: i32, text: &str) -> Result<(), TaskError> {
+        match self.find_mut(id) {
+            Some(entry) => {
+                entry.comments.push((Local::now().naive_local(), text.trim().to_string()));
+                Ok(())
+            },
+            None => Err(TaskError::NotFound(id))
+        }
+    }
+
+    /// Set how much of a task is done, clamped to 0-100, and return the stored value
+    pub fn set_progress(&mut self, id: i32, pct: i64) -> Result<u8, TaskError> {
+        let pct = pct.clamp(0, 100) as u8;
+        match self.find_mut(id) {
+            Some(entry) => {
+                entry.progress = Some(pct);
+                Ok(pct)
+            },
+            None => Err(TaskError::NotFound(id))
+        }
+    }
+
+    /// Attach a link to a task, replacing any previous one
+    pub fn set_link(&mut self, id: i32, link: &str) -> Result<(), TaskError> {
+        if !is_valid_link(link) {
+            return Err(TaskError::InvalidLink(link.to_string()))
+        }
+
+        match self.find_mut(id) {
+            Some(entry) => {
+                entry.link = Some(link.to_string());
+                Ok(())
+            },
+            None => Err(TaskError::NotFound(id))
+        }
+    }
+
+    /// Return link attached to a task
+    pub fn get_link(&self, id: i32) -> Result<&str, TaskError> {
+        match self.find(id) {
+            Some(entry) => entry.link.as_deref().ok_or(TaskError::NoLink(id)),
+            None => Err(TaskError::NotFound(id))
+        }
+    }
+
+    /// Replace tag `old` with `new` on every task, returning how many tasks changed
+    pub fn rename_tag(&mut self, old: &str, new: &str) -> usize {
+        let old = old.trim_start_matches('#');
+        let new = new.trim_start_matches('#');
+        let mut changed = 0;
+
+        for entry in self.entries.iter_mut() {
+            if !entry.tags.iter().any(|t| t.eq_ignore_ascii_case(old)) {
+                continue
+            }
+
+            // Dropping any existing copy of `new` keeps tags unique
+            entry.tags.retain(|t| !t.eq_ignore_ascii_case(old) && !t.eq_ignore_ascii_case(new));
+            if !new.is_empty() {
+                entry.tags.push(new.to_string());
+            }
+            changed += 1;
+        }
+
+        changed
+    }
+
+    /// Obtain count of open tasks by tag, untagged tasks are counted under "(untagged)"
+    pub fn count_by_tag(&self) -> HashMap<String, usize> {
+        let mut counts = HashMap::new();
+
+        for el in self.pending() {
+            if el.tags.is_empty() {
+                *counts.entry(String::from("(untagged)")).or_insert(0) += 1;
+            }
+            for tag in el.tags.iter() {
+                *counts.entry(tag.clone()).or_insert(0) += 1;
+            }
+        }
+
+        counts
+    }
+
+    /// Obtain count of tasks by status
+    pub fn get_status(&self) -> HashMap<Status, usize> {
+        let mut counts = HashMap::from([
+            (Status::Todo, 0),
+            (Status::Done, 0),
+            (Status::Overdue, 0),
+            (Status::InProgress, 0)
+        ]);
+
+        for el in self.get_all().iter() {
+            *counts.entry(el.status.clone()).or_insert(0) += 1;
+        }
+
+        counts
+    }
+
+    /// Closed and open task counts, open being everything not Done
+    pub fn done_vs_open(&self) -> (usize, usize) {
+        let counts = self.get_status();
+        let done = counts[&Status::Done];
+        let open = counts.iter()
+            .filter(|(status, _)| **status != Status::Done)
+            .map(|(_, count)| *count)
+            .sum();
+        (done, open)
+    }
+
+    /// Total time overdue tasks are past their deadline
+    pub fn total_overdue_duration(&self) -> Duration {
+        self.overdue_duration_at(Local::now().naive_local())
+    }
+
+    /// Total time overdue tasks are past their deadline at `now`
+    pub fn overdue_duration_at(&self, now: NaiveDateTime) -> Duration {
+        self.entries.iter()
+            .filter(|e| e.status == Status::Overdue)
+            .filter_map(|e| e.due_at_with(self.day_start_hour))
+            .map(|d| (now - d).max(Duration::zero()))
+            .fold(Duration::zero(), |total, d| total + d)
+    }
+
+    /// Move every overdue task's deadline to `to` and reopen it as Todo, returning how many moved
+    pub fn bump_overdue(&mut self, to: NaiveDateTime) -> usize {
+        let mut bumped = 0;
+        for entry in self.entries.iter_mut().filter(|e| e.status == Status::Overdue) {
+            entry.set_deadline(Some(to));
+            entry.deadline_offset = None;
+            entry.status = Status::Todo;
+            bumped += 1;
+        }
+        bumped
+    }
+
+    /// Move the deadline of every matching task that has one by `delta`, returning how many moved.
+    /// A deadline that would leave the date range stays as it is. Statuses are recomputed afterwards, see `reconcile`.
+    pub fn shift_deadlines<F: Fn(&Entry) -> bool>(&mut self, pred: F, delta: Duration) -> usize {
+        let mut shifted = 0;
+        for entry in self.entries.iter_mut().filter(|e| pred(e)) {
+            if let Some(moved) = entry.deadline.and_then(|d| d.checked_add_signed(delta)) {
+                entry.set_deadline(Some(moved));
+                entry.deadline_offset = None;
+                shifted += 1;
+            }
+        }
+        self.reconcile();
+        shifted
+    }
+
+    /// Recompute Todo and Overdue statuses from the deadlines, returning how many changed
+    pub fn reconcile(&mut self) -> usize {
+        self.reconcile_at(Local::now().naive_local(), Duration::zero())
+    }
+
+    /// Recompute Todo and Overdue statuses at `now`, started and closed tasks are left alone
+    pub fn reconcile_at(&mut self, now: NaiveDateTime, grace: Duration) -> usize {
+        let mut changed = 0;
+        for entry in self.entries.iter_mut() {
+            let status = match entry.status {
+                Status::Todo | Status::Overdue if entry.is_overdue_at(now, grace, self.day_start_hour) => Status::Overdue,
+                Status::Todo | Status::Overdue => Status::Todo,
+                _ => continue
+            };
+            if entry.status != status {
+                entry.status = status;
+                changed += 1;
+            }
+        }
+        changed
+    }
+
+    pub fn check_overdues(&mut self) {
+        self.check_overdues_after(Duration::zero());
+    }
+
+    /// Advance the deadlines of recurring tasks that were missed, returning how many tasks moved
+    pub fn nudge_recurring(&mut self, now: NaiveDateTime, grace: Duration) -> usize {
+        self.entries.iter_mut()
+            .map(|e| e.nudge_at(now, grace, self.day_start_hour))
+            .filter(|skipped| *skipped > 0)
+            .count()
+    }
+
+    /// Open tasks sharing a name, ignoring case and surrounding spaces, with the task each would merge into.
+    /// The survivor of a group is the one with the earliest deadline, or the earliest created without deadlines.
+    pub fn duplicates(&self) -> Vec<(i32, i32)> {
+        let mut groups: HashMap<String, Vec<&Entry>> = HashMap::new();
+        for entry in self.pending() {
+            groups.entry(entry.get_name().trim().to_lowercase()).or_default().push(entry);
+        }
+
+        let mut pairs = Vec::new();
+        for group in groups.values().filter(|g| g.len() > 1) {
+            let keep = group.iter()
+                .min_by_key(|e| (e.deadline.is_none(), e.deadline, e.timestamp, e.id))
+                .map(|e| e.id)
+                .unwrap();
+            pairs.extend(group.iter().filter(|e| e.id != keep).map(|e| (e.id, keep)));
+        }
+        pairs.sort();
+        pairs
+    }
+
+    /// Remove open duplicates as found by `duplicates`, returning how many were removed.
+    /// Subtasks and dependencies of a removed task move to the one kept.
+    pub fn merge_duplicates(&mut self) -> usize {
+        let pairs = self.duplicates().into_iter().collect::<HashMap<i32, i32>>();
+        let kept = |id: i32| pairs.get(&id).copied().unwrap_or(id);
+
+        self.entries.retain(|e| !pairs.contains_key(&e.id));
+        for entry in self.entries.iter_mut() {
+            entry.parent = entry.parent.map(kept);
+            // Merged blockers need not be next to each other, keep the first of each
+            let mut seen = std::collections::HashSet::new();
+            entry.blocked_by = entry.blocked_by.iter().map(|id| kept(*id)).filter(|id| *id != entry.id && seen.insert(*id)).collect();
+        }
+        pairs.len()
+    }
+
+    /// Delete a task and return it, subtasks lose their parent and waiting tasks stop waiting on it.
+    /// Other ids are not affected.
+    pub fn remove_task(&mut self, id: i32) -> Result<Entry, TaskError> {
+        let index = self.entries.iter().position(|e| e.id == id).ok_or(TaskError::NotFound(id))?;
+        let removed = self.entries.remove(index);
+        for entry in self.entries.iter_mut() {
+            if entry.parent == Some(id) {
+                entry.parent = None;
+            }
+            entry.blocked_by.retain(|blocker| *blocker != id);
+        }
+        Ok(removed)
+    }
+
+    /// Remind about a task this long before its deadline, or stop reminding with `None`
+    pub fn set_reminder(&mut self, id: i32, lead: Option<Duration>) -> Result<(), TaskError> {
+        match self.find_mut(id) {
+            Some(entry) => {
+                entry.remind_before = lead;
+                Ok(())
+            },
+            None => Err(TaskError::NotFound(id))
+        }
+    }
+
+    /// Open tasks whose reminder is due at `now`, earliest deadline first
+    pub fn reminders_at(&self, now: NaiveDateTime) -> Vec<&Entry> {
+        let mut due = self.pending().into_iter()
+            .filter(|e| e.should_remind_at(now, self.day_start_hour))
+            .collect::<Vec<&Entry>>();
+        due.sort_by_key(|e| e.deadline);
+        due
+    }
+
+    /// Make a task come back every day or week, or stop it with `None`
+    pub fn set_recurrence(&mut self, id: i32, recurrence: Option<Recurrence>) -> Result<(), TaskError> {
+        match self.find_mut(id) {
+            Some(entry) => {
+                entry.recurrence = recurrence;
+                Ok(())
+            },
+            None => Err(TaskError::NotFound(id))
+        }
+    }
+
+    /// Todo tasks that should already be Overdue at `now`, zero right after `check_overdues_after`
+    pub fn stale_overdues_at(&self, now: NaiveDateTime, grace: Duration) -> usize {
+        self.with_status(Status::Todo).filter(|e| e.is_overdue_at(now, grace, self.day_start_hour)).count()
+    }
+
+    /// Mark Todo tasks past deadline by more than `grace` as overdue, started tasks keep their status.
+    /// Missed recurring tasks move to their next occurrence instead.
+    pub fn check_overdues_after(&mut self, grace: Duration) {
+        self.nudge_recurring(Local::now().naive_local(), grace);
+        for i in 0..self.get_size() {
+            if self.entries[i].status == Status::Todo && self.entries[i].is_overdue_after(grace, self.day_start_hour) {
+                self.entries[i].status = Status::Overdue;
+            }
+        }
+    }
+}
+
+/// Check that a link uses the http or https scheme
+pub fn is_valid_link(link: &str) -> bool {
+    let rest = link.strip_prefix("https://").or_else(|| link.strip_prefix("http://"));
+    matches!(rest, Some(r) if !r.is_empty() && !r.contains(char::is_whitespace))
+}
+
+/// Source of random numbers, seedable for tests
+pub trait Rng {
+    fn next_u64(&mut self) -> u64;
+}
+
+/// Small xorshift generator, good enough to pick a task
+pub struct XorShift(u64);
+
+impl XorShift {
+    pub fn new(seed: u64) -> Self {
+        // Zero is a fixed point of xorshift
+        XorShift(seed.max(1))
+    }
+
+    /// Seed from the current time
+    pub fn from_time() -> Self {
+        XorShift::new(Local::now().timestamp_nanos_opt().unwrap_or_default() as u64)
+    }
+}
+
+impl Rng for XorShift {
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+/// Fields of a task carried by a share token
+#[derive(Serialize, Deserialize)]
+struct SharedTask {
+    task: String,
+    deadline: Option<NaiveDateTime>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    priority: Priority
+}
+
+/// Encode a task's name, deadline, tags and priority as a single line token
+pub fn encode_task(entry: &Entry) -> String {
+    use base64::Engine;
+
+    let shared = SharedTask {
+        task: entry.get_name().to_string(),
+        deadline: entry.deadline,
+        tags: entry.tags.clone(),
+        priority: entry.priority
+    };
+    let json = serde_json::to_vec(&shared).expect("Error serializing task");
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(json)
+}
+
+/// Decode a token made by `encode_task` into a new Todo task with id 0
+pub fn decode_task(token: &str) -> Result<Entry, TaskError> {
+    use base64::Engine;
+
+    let json = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(token.trim())
+        .map_err(|_| TaskError::InvalidToken)?;
+    let shared: SharedTask = serde_json::from_slice(&json).map_err(|_| TaskError::InvalidToken)?;
+    if shared.task.trim().is_empty() {
+        return Err(TaskError::EmptyName)
+    }
+
+    let mut entry = Entry::new(0, shared.task, shared.deadline);
+    entry.tags = shared.tags;
+    entry.priority = shared.priority;
+    Ok(entry)
+}
+
+/// Runs shell commands, e.g. the `on_complete` hook
+pub trait CommandRunner {
+    fn run(&self, command: &str) -> Result<(), Error>;
+}
+
+/// Runs commands through `sh`, task names are quoted for it so other shells are refused
+pub struct ShellRunner;
+
+impl CommandRunner for ShellRunner {
+    fn run(&self, command: &str) -> Result<(), Error> {
+        if cfg!(target_os = "windows") {
+            return Err(Error::new(ErrorKind::Unsupported, "on_complete hooks need a POSIX shell"))
+        }
+
+        // The hook runs in the background, waiting on it keeps it from lingering as a zombie
+        let mut child = Command::new("sh").arg("-c").arg(command).spawn()?;
+        std::thread::spawn(move || {
+            let _ = child.wait();
+        });
+        Ok(())
+    }
+}
+
+/// Quote text as a single POSIX shell word
+fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+/// Fill the `{task}` placeholder of a hook with the quoted task name
+pub fn expand_hook(template: &str, task: &str) -> String {
+    template.replace("{task}", &shell_quote(task))
+}
+
+/// Run the `on_complete` hook for a closed task, only warning if it can't be started
+pub fn run_on_complete(template: Option<&str>, task: &str, runner: &dyn CommandRunner) {
+    if let Some(template) = template {
+        let command = expand_hook(template, task);
+        if let Err(e) = runner.run(&command) {
+            println!("Could not run on_complete command '{}': {}", command, e);
+        }
+    }
+}
+
+/// Opens links outside of the app
+pub trait Opener {
+    fn open(&self, link: &str) -> Result<(), Error>;
+}
+
+/// Opens links in the default browser of the OS
+pub struct BrowserOpener;
+
+impl Opener for BrowserOpener {
+    fn open(&self, link: &str) -> Result<(), Error> {
+        let mut command = if cfg!(target_os = "macos") {
+            Command::new("open")
+        }
+        else if cfg!(target_os = "windows") {
+            let mut c = Command::new("cmd");
+            c.args(["/C", "start", ""]);
+            c
+        }
+        else {
+            Command::new("xdg-open")
+        };
+
+        command.arg(link).spawn().map(|_| ())
+    }
+}
+
+/// Open the link attached to a task
+pub fn open_link(list: &List, id: i32, opener: &dyn Opener) -> Result<(), String> {
+    let link = list.get_link(id).map_err(|e| e.to_string())?;
+    opener.open(link).map_err(|e| format!("Could not open {}: {}", link, e))
+}
+
+/// Changes between two versions of a task list, by task id
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct ListDiff {
+    pub added: Vec<i32>,
+    pub closed: Vec<i32>,
+    pub removed: Vec<i32>,
+    pub renamed: Vec<i32>
+}
+
+impl ListDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.closed.is_empty() && self.removed.is_empty() && self.renamed.is_empty()
+    }
+}
+
+impl fmt::Display for ListDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f, "This session: {} added, {} closed, {} removed, {} renamed",
+            self.added.len(), self.closed.len(), self.removed.len(), self.renamed.len()
+        )
+    }
+}
+
+/// Compare two versions of a task list
+pub fn diff(before: &List, after: &List) -> ListDiff {
+    let mut changes = ListDiff::default();
+
+    for old in before.get_all().iter() {
+        match after.get_all().iter().find(|e| e.id == old.id) {
+            Some(new) => {
+                if old.status != Status::Done && new.status == Status::Done {
+                    changes.closed.push(new.id);
+                }
+                if old.task.trim() != new.task.trim() {
+                    changes.renamed.push(new.id);
+                }
+            },
+            None => changes.removed.push(old.id)
+        }
+    }
+
+    for new in after.get_all().iter() {
+        if !before.get_all().iter().any(|e| e.id == new.id) {
+            changes.added.push(new.id);
+            if new.status == Status::Done {
+                changes.closed.push(new.id);
+            }
+        }
+    }
+
+    changes
+}
+
+/// How many tasks of the file on disk are neither in memory nor in the list loaded at startup,
+/// i.e. tasks another instance saved meanwhile. Tasks match by id and creation time.
+pub fn unseen_on_disk(on_disk: &List, memory: &List, loaded: &List) -> usize {
+    let known = |list: &List, entry: &Entry| list.entries.iter().any(|e| e.id == entry.id && e.timestamp == entry.timestamp);
+    on_disk.entries.iter()
+        .filter(|e| !known(memory, e) && !known(loaded, e))
+        .count()
+}
+
+/// Parse a task list from any reader, as JSON unless `TODO_FORMAT` says otherwise.
+/// Encrypted content is decrypted with `TODO_PASSPHRASE`.
+pub fn read_list(reader: impl Read) -> Result<List, Error> {
+    let format = StorageFormat::resolve(storage_format().as_deref(), "");
+    parse_list(reader, "input", format, passphrase().as_deref())
+}
+
+/// Read a task file in the format chosen by `TODO_FORMAT` or its extension.
+/// Encrypted files need the passphrase they were written with.
+pub fn load_list(fpath: &str, passphrase: Option<&str>) -> Result<List, Error> {
+    let format = StorageFormat::resolve(storage_format().as_deref(), fpath);
+    check_file_size(fpath, max_file_bytes())?;
+    let mut list = parse_list(fs::File::open(fpath)?, fpath, format, passphrase)?;
+    if list.last_modified.is_none() {
+        list.last_modified = fs::metadata(fpath)?.modified().ok()
+            .map(|mtime| chrono::DateTime::<Local>::from(mtime).naive_local());
+    }
+    Ok(list)
+}
+
+/// Refuse to read a task file larger than `cap` bytes rather than loading it all in memory
+fn check_file_size(fpath: &str, cap: u64) -> Result<(), Error> {
+    let size = fs::metadata(fpath)?.len();
+    if size > cap {
+        return Err(Error::other(format!(
+            "{} is {} bytes, over the {} byte limit, archive closed tasks or raise TODO_MAX_FILE_MB",
+            fpath, size, cap
+        )))
+    }
+    Ok(())
+}
+
+/// Shared by `read_list` and `load_list`, `source` names the input in errors
+fn parse_list(mut reader: impl Read, source: &str, format: StorageFormat, passphrase: Option<&str>) -> Result<List, Error> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+
+    if crypto::is_encrypted(&data) {
+        let passphrase = passphrase.ok_or_else(|| {
+            Error::new(ErrorKind::PermissionDenied, format!("{} is encrypted, set TODO_PASSPHRASE", source))
+        })?;
+        data = crypto::decrypt(&data, passphrase)?;
+    }
+
+    format.deserialize(&data)
+}
+
+/// Fail when `fpath` exists but is a directory or other non-file, e.g. a folder named `tasks.json`
+fn ensure_regular_file(fpath: &str) -> Result<(), Error> {
+    match fs::metadata(fpath) {
+        Ok(meta) if !meta.is_file() => Err(Error::new(ErrorKind::InvalidInput, format!("{} is not a regular file", fpath))),
+        _ => Ok(())
+    }
+}
+
+/// Copy of a damaged task file kept by `read_or_create`, `tasks.json` is kept as `tasks.json.corrupt`
+pub fn corrupt_path(fpath: &str) -> String {
+    format!("{}.corrupt", fpath)
+}
+
+/// Reads task file or creates a new task list if there is no file.
+/// A damaged plain JSON file keeps whatever tasks can still be read, the file itself is copied to `corrupt_path`.
+pub fn read_or_create(fpath: &str) -> Result<List, Error> {
+    if !Path::new(fpath).exists() {
+        return Ok(List::new())
+    }
+    ensure_regular_file(fpath)?;
+
+    match load_list(fpath, passphrase().as_deref()) {
+        // Missed recurring tasks are moved by `check_overdues_after`, which honours the grace period
+        Ok(list) => Ok(list),
+        Err(e) if e.kind() == ErrorKind::InvalidData => {
+            let data = fs::read(fpath)?;
+            let json = StorageFormat::resolve(storage_format().as_deref(), fpath) == StorageFormat::Json;
+            if crypto::is_encrypted(&data) || !json {
+                return Err(e)
+            }
+
+            // Saving the recovered list replaces the file, keep the original for what the scan missed
+            let backup = corrupt_path(fpath);
+            fs::write(&backup, &data)?;
+            let list = recover_list(&String::from_utf8_lossy(&data));
+            println!(
+                "{} could not be read ({}), recovered {}, the original is kept as {}",
+                fpath, e, pluralize(list.get_size(), "task", "tasks"), backup
+            );
+            Ok(list)
+        },
+        Err(e) => Err(e)
+    }
+}
+
+/// Salvage the tasks of a damaged task file, e.g. one cut off mid-write.
+/// Every complete object in the `entries` array that parses as a task is kept.
+pub fn recover_list(content: &str) -> List {
+    let mut list = List::new();
+    let start = match content.find("\"entries\"").and_then(|i| content[i..].find('[').map(|j| i + j + 1)) {
+        Some(start) => start,
+        None => return list
+    };
+
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut object_start = None;
+
+    for (i, c) in content[start..].char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => ()
+            }
+            continue
+        }
+
+        match c {
+            '"' => in_string = true,
+            '{' => {
+                if depth == 0 {
+                    object_start = Some(start + i);
+                }
+                depth += 1;
+            },
+            '}' if depth > 0 => {
+                depth -= 1;
+                if let (0, Some(from)) = (depth, object_start.take()) {
+                    if let Ok(entry) = serde_json::from_str::<Entry>(&content[from..=start + i]) {
+                        let _ = list.push_entry(entry);
+                    }
+                }
+            },
+            // End of the entries array
+            ']' if depth == 0 => break,
+            _ => ()
+        }
+    }
+    list
+}
+
+/// Save task list in the format chosen by `TODO_FORMAT` or the extension, encrypted when `TODO_PASSPHRASE` is set.
+/// A file that can't be written is an error, see `export_fallback` for saving it elsewhere.
+pub fn export(list: List, fpath: &str) -> Result<(), Error> {
+    export_with(list, fpath, passphrase().as_deref())
+}
+
+/// Copy the permission bits of the file being replaced onto its new version
+#[cfg(unix)]
+fn restore_permissions(file: &fs::File, previous: Option<fs::Permissions>) -> Result<(), Error> {
+    match previous {
+        Some(perms) => file.set_permissions(perms),
+        None => Ok(())
+    }
+}
+
+/// Write the temporary file, with the original's permissions applied before any content lands in it
+fn write_tmp(tmp: &str, content: &[u8], #[cfg(unix)] previous: Option<fs::Permissions>) -> Result<(), Error> {
+    let mut file = fs::File::create(tmp)?;
+    #[cfg(unix)]
+    restore_permissions(&file, previous)?;
+    file.write_all(content)
+}
+
+/// Replace a file by writing a temporary sibling and renaming it over the original
+fn write_replace(fpath: &str, content: &[u8]) -> Result<(), Error> {
+    #[cfg(unix)]
+    return write_replace_with(fpath, content, fs::metadata(fpath).ok().map(|m| m.permissions()));
+    #[cfg(not(unix))]
+    return write_replace_with(fpath, content);
+}
+
+/// Same as `write_replace`, the new file gets the permissions `previous` rather than the original's
+fn write_replace_with(fpath: &str, content: &[u8], #[cfg(unix)] previous: Option<fs::Permissions>) -> Result<(), Error> {
+    let tmp = format!("{}.tmp", fpath);
+    #[cfg(unix)]
+    let written = write_tmp(&tmp, content, previous);
+    #[cfg(not(unix))]
+    let written = write_tmp(&tmp, content);
+
+    if let Err(e) = written.and_then(|_| fs::rename(&tmp, fpath)) {
+        let _ = fs::remove_file(&tmp);
+        return Err(e)
+    }
+    Ok(())
+}
+
+/// Save task list to file, encrypting it if a passphrase is given. Sets `last_modified` to now.
+pub fn export_with(list: List, fpath: &str, passphrase: Option<&str>) -> Result<(), Error> {
+    ensure_regular_file(fpath)?;
+    write_replace(fpath, &encode_list(list, fpath, passphrase)?)
+}
+
+/// Content of the task file at `fpath`, see `export_with`
+fn encode_list(mut list: List, fpath: &str, passphrase: Option<&str>) -> Result<Vec<u8>, Error> {
+    list.last_modified = Some(Local::now().naive_local());
+    let data = StorageFormat::resolve(storage_format().as_deref(), fpath).serialize(&list)?;
+    Ok(match passphrase {
+        Some(passphrase) => crypto::encrypt(&data, passphrase),
+        None => data
+    })
+}
+
+/// Where `export_fallback` saves a task file that can't be written, the same name in the temp directory
+pub fn fallback_path(fpath: &str) -> PathBuf {
+    let name = Path::new(fpath).file_name().unwrap_or("tasks.json".as_ref());
+    std::env::temp_dir().join(name)
+}
+
+/// Save task list to `fallback_path` instead of `fpath`, returning the path written.
+/// The copy gets the permissions of the file at `fpath`, or is readable by its owner only.
+pub fn export_fallback(list: List, fpath: &str) -> Result<PathBuf, Error> {
+    let fallback = fallback_path(fpath);
+    let target = fallback.to_string_lossy();
+    ensure_regular_file(&target)?;
+    let data = encode_list(list, fpath, passphrase().as_deref())?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let perms = fs::metadata(fpath).map(|m| m.permissions()).unwrap_or_else(|_| fs::Permissions::from_mode(0o600));
+        write_replace_with(&target, &data, Some(perms))?;
+    }
+    #[cfg(not(unix))]
+    write_replace_with(&target, &data)?;
+    Ok(fallback)
+}
+
+/// Archive kept next to a task file, `tasks.json` archives to `tasks.archive.jsonl`
+pub fn archive_path(fpath: &str) -> String {
+    Path::new(fpath).with_extension("archive.jsonl").to_string_lossy().to_string()
+}
+
+/// Move closed tasks to the end of the archive, one JSON object per line, and return how many were moved.
+/// Nothing is removed from the list unless the archive was written.
+pub fn archive_done(list: &mut List, archive_path: &str) -> Result<usize, Error> {
+    let done = list.entries.iter().filter(|e| e.status == Status::Done).collect::<Vec<&Entry>>();
+    if done.is_empty() {
+        return Ok(0)
+    }
+
+    let mut lines = String::new();
+    for entry in done.iter() {
+        lines.push_str(&serde_json::to_string(entry)?);
+        lines.push('\n');
+    }
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(archive_path)?;
+    file.write_all(lines.as_bytes())?;
+
+    let moved = done.len();
+    list.entries.retain(|e| e.status != Status::Done);
+    Ok(moved)
+}
+
+/// Path of the lock file guarding a task file
+pub fn lock_path(fpath: &str) -> String {
+    format!("{}.lock", fpath)
+}
+
+/// Check whether a process is still running, assumed alive where it can't be checked
+fn process_alive(pid: u32) -> bool {
+    if cfg!(target_os = "linux") {
+        Path::new("/proc").join(pid.to_string()).exists()
+    }
+    else {
+        true
+    }
+}
+
+/// Create the lock file for a task file, replacing it if its owner is no longer running
+pub fn acquire_lock(fpath: &str) -> Result<(), Error> {
+    let lock = lock_path(fpath);
+
+    for _ in 0..2 {
+        match fs::OpenOptions::new().write(true).create_new(true).open(&lock) {
+            Ok(mut f) => return write!(f, "{}", std::process::id()),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                let owner = fs::read_to_string(&lock).ok().and_then(|pid| pid.trim().parse::<u32>().ok());
+                match owner {
+                    Some(pid) if !process_alive(pid) => fs::remove_file(&lock)?,
+                    _ => return Err(e)
+                }
+            },
+            Err(e) => return Err(e)
+        }
+    }
+
+    Err(Error::new(ErrorKind::AlreadyExists, format!("Could not acquire {}", lock)))
+}
+
+/// Remove the lock file for a task file
+pub fn release_lock(fpath: &str) -> Result<(), Error> {
+    fs::remove_file(lock_path(fpath))
+}
+
+/// Largest accepted relative deadline, in days
+const MAX_RELATIVE_DAYS: i64 = 36500;
+
+/// Parse an offset such as `+3d` or `+2w`, at most `MAX_RELATIVE_DAYS` long
+pub fn parse_offset(offset_raw: &str) -> Result<Duration, TaskError> {
+    let invalid = || TaskError::InvalidOffset(offset_raw.trim().to_string());
+    let offset = offset_raw.trim().strip_prefix('+').ok_or_else(invalid)?;
+    let (n, unit) = offset.split_at_checked(offset.len().checked_sub(1).ok_or_else(invalid)?).ok_or_else(invalid)?;
+    if n.is_empty() || !n.chars().all(|c| c.is_ascii_digit()) {
+        return Err(invalid())
+    }
+
+    // Only digits are left, so a number that doesn't parse is too long
+    let too_long = || TaskError::OffsetTooLong(MAX_RELATIVE_DAYS);
+    let n = n.parse::<i64>().map_err(|_| too_long())?;
+    let days = match unit {
+        "d" => n,
+        "w" => n.checked_mul(7).ok_or_else(too_long)?,
+        _ => return Err(invalid())
+    };
+
+    if days > MAX_RELATIVE_DAYS {
+        return Err(too_long())
+    }
+    Ok(Duration::days(days))
+}
+
+/// Parse a length of time such as `30m`, `2h`, `1d` or `1w`, at most `MAX_RELATIVE_DAYS` long
+pub fn parse_duration(duration_raw: &str) -> Option<Duration> {
+    let raw = duration_raw.trim();
+    let (n, unit) = raw.split_at_checked(raw.len().checked_sub(1)?)?;
+    if n.is_empty() || !n.chars().all(|c| c.is_ascii_digit()) {
+        return None
+    }
+
+    let n = n.parse::<i64>().ok()?;
+    let duration = match unit {
+        "m" => Duration::try_minutes(n),
+        "h" => Duration::try_hours(n),
+        "d" => Duration::try_days(n),
+        "w" => Duration::try_weeks(n),
+        _ => None
+    };
+    duration.filter(|d| *d <= Duration::days(MAX_RELATIVE_DAYS))
+}
+
+/// Parse a signed length of time such as `+7d` or `-2h`, see `parse_duration`
+pub fn parse_shift(shift_raw: &str) -> Option<Duration> {
+    let raw = shift_raw.trim();
+    match raw.strip_prefix('-') {
+        Some(back) => parse_duration(back).map(|d| -d),
+        None => parse_duration(raw.strip_prefix('+')?)
+    }
+}
+
+/// Parse a relative deadline such as `+3d` or `+2w`, counted from `now`
+pub fn parse_relative(offset_raw: &str, now: NaiveDateTime) -> Option<NaiveDateTime> {
+    now.checked_add_signed(parse_offset(offset_raw).ok()?)
+}
+
+/// Parse a Unix time in seconds written as `@1704067200`, as the local time of `tz`.
+/// The `@` keeps a bare number such as a year from being read as a timestamp.
+pub fn parse_epoch<Tz: TimeZone>(epoch_raw: &str, tz: &Tz) -> Option<NaiveDateTime> {
+    let digits = epoch_raw.trim().strip_prefix('@')?;
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None
+    }
+
+    let secs = digits.parse::<i64>().ok()?;
+    Some(chrono::DateTime::from_timestamp(secs, 0)?.with_timezone(tz).naive_local())
+}
+
+/// Parse a weekday like `fri` or `next tuesday` as the start of its next occurrence after `today`.
+/// `next` skips to the occurrence at least a week away.
+pub fn parse_weekday(weekday_raw: &str, today: NaiveDate) -> Option<NaiveDateTime> {
+    let lowered = weekday_raw.trim().to_lowercase();
+    let (skip_week, name) = match lowered.strip_prefix("next ") {
+        Some(name) => (true, name.trim()),
+        None => (false, lowered.as_str())
+    };
+    let target = name.parse::<Weekday>().ok()?;
+
+    let mut days_ahead = (target.num_days_from_monday() + 7 - today.weekday().num_days_from_monday()) % 7;
+    if days_ahead == 0 {
+        days_ahead = 7;
+    }
+    if skip_week && days_ahead < 7 {
+        days_ahead += 7;
+    }
+
+    today.checked_add_days(Days::new(days_ahead as u64))?.and_hms_opt(0, 0, 0)
+}
+
+/// Years accepted in a deadline, anything else is most likely a typo like `20244-01-01`
+pub const DEADLINE_YEARS: std::ops::RangeInclusive<i32> = 1970..=2200;
+
+/// Parse a deadline as a date, an offset, a weekday or a Unix time, see `parse_deadline_raw`.
+/// Deadlines outside `DEADLINE_YEARS` are rejected, weekdays count from the day starting at `day_start_hour`.
+pub fn parse_deadline(deadline_raw: String, day_start_hour: u32) -> Option<NaiveDateTime> {
+    parse_deadline_raw(deadline_raw, day_start_hour).filter(|d| DEADLINE_YEARS.contains(&d.year()))
+}
+
+fn parse_deadline_raw(mut deadline_raw: String, day_start_hour: u32) -> Option<NaiveDateTime> {
+    if deadline_raw.ends_with('\n') {
+        deadline_raw.pop();
+    }
+
+    if deadline_raw.starts_with('+') {
+        return parse_relative(&deadline_raw, Local::now().naive_local())
+    }
+
+    if deadline_raw.starts_with('@') {
+        return parse_epoch(&deadline_raw, &Local)
+    }
+
+    if deadline_raw.starts_with(|c: char| c.is_alphabetic()) {
+        return parse_weekday(&deadline_raw, logical_today(Local::now().naive_local(), day_start_hour))
+    }
+
+    // A single kind of separator is allowed per date
+    let separators = ['-', '/', '.'].into_iter()
+        .filter(|sep| deadline_raw.contains(*sep))
+        .collect::<Vec<char>>();
+    if separators.len() != 1 {
+        return None
+    }
+
+    let parts = deadline_raw.split(separators[0]).collect::<Vec<&str>>();
+
+    if parts.len() != 3 {
+        return None
+    }
+
+    let year = parts[0].to_string().parse::<i32>().ok();
+    let month = parts[1].to_string().parse::<u32>().ok();
+    let day = parts[2].to_string().parse::<u32>().ok();
+
+    match (year, month, day) {
+        (Some(year), Some(month), Some(day)) => start_of_day(&Local, NaiveDate::from_ymd_opt(year, month, day)?),
+        _ => None
+    }
+}
+
+/// First valid local time of a date, midnight unless a DST change skips or repeats it
+pub fn start_of_day<Tz: TimeZone>(tz: &Tz, date: NaiveDate) -> Option<NaiveDateTime> {
+    match tz.from_local_datetime(&date.and_time(NaiveTime::MIN)) {
+        LocalResult::Single(t) => Some(t.naive_local()),
+        LocalResult::Ambiguous(earliest, _) => {
+            println!("Midnight of {} occurs twice, using the earlier one", date);
+            Some(earliest.naive_local())
+        },
+        LocalResult::None => {
+            let first_valid = (1..24)
+                .filter_map(|h| date.and_hms_opt(h, 0, 0))
+                .find(|t| tz.from_local_datetime(t).earliest().is_some());
+            match first_valid {
+                Some(t) => {
+                    println!("Midnight of {} does not exist, using {}", date, t.time());
+                    Some(t)
+                },
+                None => {
+                    println!("No valid time found for {}, using noon", date);
+                    date.and_hms_opt(12, 0, 0)
+                }
+            }
+        }
+    }
+}
+
+/// Format a duration as its two largest units, e.g. "2d 3h" or "45m"
+fn format_span(d: Duration) -> String {
+    let days = d.num_days();
+    let hours = d.num_hours() % 24;
+    let minutes = d.num_minutes() % 60;
+
+    if days > 0 {
+        if hours > 0 { format!("{}d {}h", days, hours) } else { format!("{}d", days) }
+    }
+    else if hours > 0 {
+        if minutes > 0 { format!("{}h {}m", hours, minutes) } else { format!("{}h", hours) }
+    }
+    else {
+        format!("{}m", minutes)
+    }
+}
+
+/// Describe time left until a deadline, or how long ago it passed when `overdue`
+pub fn humanize_remaining(d: Option<Duration>, overdue: bool) -> String {
+    match d {
+        None => String::from("no deadline"),
+        Some(d) if overdue => format!("overdue by {}", format_span(d)),
+        Some(d) if d < Duration::hours(1) => String::from("due soon"),
+        Some(d) => format!("in {}", format_span(d))
+    }
+}
+
+/// Count followed by the singular or plural noun, e.g. "1 task", "2 tasks"
+pub fn pluralize(n: usize, singular: &str, plural: &str) -> String {
+    if n == 1 {
+        format!("{} {}", n, singular)
+    }
+    else {
+        format!("{} {}", n, plural)
+    }
+}
+
+/// Wrap text in an ANSI color code such as `31` for red, returned unchanged when color is off
+pub fn colorize(text: &str, code: &str, enabled: bool) -> String {
+    if enabled {
+        format!("\x1b[{}m{}\x1b[0m", code, text)
+    }
+    else {
+        text.to_string()
+    }
+}
+
+/// Id of the task shown at a 1-based `menu` position, `None` for anything else
+pub fn menu_task_id(ids: &[i32], choice: &str) -> Option<i32> {
+    let position = choice.trim().parse::<usize>().ok()?;
+    ids.get(position.checked_sub(1)?).copied()
+}
+
+/// Check whether unsaved changes have waited long enough to be written, never without a threshold
+pub fn should_autosave(idle: std::time::Duration, threshold: Option<std::time::Duration>, dirty: bool) -> bool {
+    dirty && threshold.is_some_and(|threshold| idle >= threshold)
+}
+
+/// Check whether the open task count is past the work in progress limit, if there is one
+pub fn over_wip_limit(open: usize, limit: Option<usize>) -> bool {
+    limit.is_some_and(|limit| open > limit)
+}
+
+/// How long ago `past` was, e.g. "5m ago" or "2w ago", as a date once it is over 8 weeks back
+pub fn humanize_ago(past: NaiveDateTime, now: NaiveDateTime) -> String {
+    let elapsed = now - past;
+
+    if elapsed < Duration::minutes(1) {
+        String::from("just now")
+    }
+    else if elapsed < Duration::hours(1) {
+        format!("{}m ago", elapsed.num_minutes())
+    }
+    else if elapsed < Duration::days(1) {
+        format!("{}h ago", elapsed.num_hours())
+    }
+    else if elapsed < Duration::weeks(2) {
+        format!("{}d ago", elapsed.num_days())
+    }
+    else if elapsed <= Duration::weeks(8) {
+        format!("{}w ago", elapsed.num_weeks())
+    }
+    else {
+        past.format("%Y-%m-%d").to_string()
+    }
+}
+
+/// One line overview of open tasks, e.g. "3 todo / 1 overdue"
+pub fn summary_line(list: &List) -> String {
+    let pending = list.pending();
+    let overdue = pending.iter().filter(|e| e.status == Status::Overdue).count();
+    format!("{} todo / {} overdue", pending.len() - overdue, overdue)
+}
+
+/// Task file, list defaults and counts as one `key=value` line for shell prompts.
+/// There are no projects, so none is shown.
+pub fn context_line(config: &Config, list: &List) -> String {
+    let pending = list.pending();
+    let overdue = pending.iter().filter(|e| e.status == Status::Overdue).count();
+    format!(
+        "file={} sort={} hide_done={} open={} overdue={}",
+        config.file_path, config.default_sort.name(), config.hide_done, pending.len(), overdue
+    )
+}
+
+/// Describe how far behind overdue tasks are in total
+pub fn debt_line(list: &List) -> String {
+    let overdue = list.get_status()[&Status::Overdue];
+    if overdue == 0 {
+        return String::from("You have no overdue tasks")
+    }
+    format!(
+        "You're {} behind across {}",
+        format_span(list.total_overdue_duration()), pluralize(overdue, "task", "tasks")
+    )
+}
+
+/// Parse a `YYYY-MM` month
+pub fn parse_month(raw: &str) -> Option<(i32, u32)> {
+    let (year, month) = raw.trim().split_once('-')?;
+    let year = year.parse::<i32>().ok()?;
+    let month = month.parse::<u32>().ok().filter(|m| (1..=12).contains(m))?;
+    Some((year, month))
+}
+
+/// Tasks added and closed in a month, and how the open count changed
+pub fn month_report(list: &List, year: i32, month: u32) -> String {
+    let created = list.created_in_month(year, month).len();
+    let completed = list.completed_in_month(year, month).len();
+    format!(
+        "{}-{:02}: {} created, {} completed, net {:+}",
+        year, month, created, completed, created as i64 - completed as i64
+    )
+}
+
+/// One bar per week, e.g. `2024-01-08 ### 3`
+pub fn forecast_chart(histogram: &[(NaiveDate, usize)]) -> String {
+    histogram.iter()
+        .map(|(week, count)| format!("{} {} {}", week, "#".repeat(*count), count))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// One bar per weekday, e.g. `Mon ### 3`
+pub fn habits_chart(counts: &[usize; 7]) -> String {
+    let days = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+    days.iter().zip(counts.iter())
+        .map(|(day, count)| format!("{} {} {}", day, "#".repeat(*count), count))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Cheer for a streak longer than a day, e.g. `🔥 3-day streak!`
+pub fn streak_message(streak: usize, marker: &str) -> Option<String> {
+    if streak > 1 {
+        Some(format!("{} {}-day streak!", marker, streak))
+    }
+    else {
+        None
+    }
+}
+
+/// Diagnostic printed with `TODO_DEBUG`, e.g. `[debug] load: 1.2ms, 40 tasks`
+pub fn timing_line(stage: &str, elapsed: std::time::Duration, tasks: usize) -> String {
+    format!("[debug] {}: {:.1?}, {}", stage, elapsed, pluralize(tasks, "task", "tasks"))
+}
+
+/// Share of all tasks that are closed, e.g. `3/4 done [########--] 75%`
+pub fn overall_progress(list: &List) -> String {
+    let (done, open) = list.done_vs_open();
+    let total = done + open;
+    let pct = (done * 100).checked_div(total).unwrap_or(0);
+    format!("{}/{} done {}", done, total, progress_bar(pct as u8))
+}
+
+/// Ten cell bar for a percentage, e.g. `[####------] 40%`
+pub fn progress_bar(pct: u8) -> String {
+    let pct = pct.min(100);
+    let filled = (pct as usize + 5) / 10;
+    format!("[{}{}] {}%", "#".repeat(filled), "-".repeat(10 - filled), pct)
+}
+
+/// Print all details of a task, dates use the chrono `date_format` and days start at `day_start_hour`
+pub fn show_task(entry: &Entry, date_format: &str, day_start_hour: u32) {
+    println!("Task {}: {}", entry.id, entry.get_name());
+    println!("Status: {:?}", entry.status);
+    println!("Priority: {:?}", entry.priority);
+    let now = Local::now().naive_local();
+    println!("Created: {} ({})", entry.timestamp.format(date_format), humanize_ago(entry.timestamp, now));
+    match entry.deadline {
+        Some(d) => println!("Deadline: {} ({})", d.format(date_format), entry.remaining_display(day_start_hour)),
+        None => println!("Deadline: none")
+    }
+    if let Some(pct) = entry.progress {
+        println!("Progress: {}", progress_bar(pct));
+    }
+    if let Some(completed_at) = entry.completed_at {
+        match entry.completed_via {
+            Some(reason) => println!("Closed: {} ({}, {:?})", completed_at.format(date_format), humanize_ago(completed_at, now), reason),
+            None => println!("Closed: {} ({})", completed_at.format(date_format), humanize_ago(completed_at, now))
+        }
+    }
+    if let Some(parent) = entry.parent {
+        println!("Subtask of: {}", parent);
+    }
+    if !entry.tags.is_empty() {
+        println!("Tags: {}", entry.tags.join(", "));
+    }
+    if let Some(link) = &entry.link {
+        println!("Link: {}", link);
+    }
+    if let Some(lead) = entry.remind_before {
+        println!("Reminder: {} before the deadline", format_span(lead));
+    }
+    if let Some(recurrence) = entry.recurrence {
+        println!("Repeats: {:?}, {} missed", recurrence, entry.skipped);
+    }
+    if !entry.comments.is_empty() {
+        let mut comments = entry.comments.iter().collect::<Vec<&(NaiveDateTime, String)>>();
+        comments.sort_by_key(|(at, _)| *at);
+        println!("Comments:");
+        for (at, text) in comments {
+            println!("  {} {}", at.format(date_format), text);
+        }
+    }
+}
+
+/// Render a group of tasks, indenting subtasks under their parent when it is in the same group.
+/// Tasks in `blocked` are marked as such.
+pub fn render_group(group: &[Entry], theme: &Theme, blocked: &[i32]) -> Vec<String> {
+    fn render_line(el: &Entry, theme: &Theme, blocked: &[i32]) -> String {
+        if blocked.contains(&el.id) {
+            format!("{} [blocked]", el.render(theme))
+        }
+        else {
+            el.render(theme)
+        }
+    }
+
+    fn render_children(group: &[Entry], parent: i32, depth: usize, theme: &Theme, blocked: &[i32], lines: &mut Vec<String>) {
+        for el in group.iter().filter(|e| e.parent == Some(parent)) {
+            lines.push(format!("{}{}", "  ".repeat(depth), render_line(el, theme, blocked)));
+            render_children(group, el.id, depth + 1, theme, blocked, lines);
+        }
+    }
+
+    let mut lines = Vec::new();
+    for el in group.iter() {
+        let is_root = match el.parent {
+            Some(parent) => !group.iter().any(|e| e.id == parent),
+            None => true
+        };
+        if is_root {
+            lines.push(render_line(el, theme, blocked));
+            render_children(group, el.id, 1, theme, blocked, &mut lines);
+        }
+    }
+    lines
+}
+
+/// Most recently closed first, tasks closed before completion times were kept go last
+pub fn sort_by_completion(entries: &mut [Entry]) {
+    entries.sort_by_key(|e| (e.completed_at.is_none(), std::cmp::Reverse(e.completed_at)));
+}
+
+/// Render all tasks grouped by status, one line each
+pub fn render_tasks(list: &List, theme: &Theme, sort: SortKey, hide_done: bool) -> String {
+    let mut overdues: Vec<Entry> = Vec::new();
+    let mut in_progress: Vec<Entry> = Vec::new();
+    let mut todos: Vec<Entry> = Vec::new();
+    let mut dones: Vec<Entry> = Vec::new();
+    let mut someday = 0;
+
+    for el in list.get_all().iter() {
+        match el.status {
+            _ if el.is_someday() => someday += 1,
+            Status::Done => dones.push(el.clone()),
+            // Without a deadline a task can't be overdue, it's listed as the todo it is
+            Status::Overdue if el.deadline.is_none() => {
+                let mut todo = el.clone();
+                todo.status = Status::Todo;
+                todos.push(todo);
+            },
+            Status::Overdue => overdues.push(el.clone()),
+            Status::Todo => todos.push(el.clone()),
+            Status::InProgress => in_progress.push(el.clone())
+        }
+    }
+
+    for group in [&mut overdues, &mut in_progress, &mut todos, &mut dones] {
+        sort.sort(group);
+    }
+    // Longest overdue first, whatever the sort key
+    SortKey::Deadline.sort(&mut overdues);
+    sort_by_completion(&mut dones);
+    let blocked = list.blocked_ids();
+
+    let mut lines = Vec::new();
+    if overdues.is_empty() {
+        lines.push(String::from("You have no overdue tasks"));
+    }
+    else {
+        lines.extend(render_group(&overdues, theme, &blocked));
+    }
+
+    lines.extend(render_group(&in_progress, theme, &blocked));
+
+    if todos.is_empty() {
+        lines.push(String::from("You have no tasks"));
+    }
+    else {
+        lines.extend(render_group(&todos, theme, &blocked));
+    }
+
+    if someday > 0 {
+        lines.push(format!("{} in Someday/Maybe, see list someday", pluralize(someday, "task", "tasks")));
+    }
+
+    if !dones.is_empty() && !hide_done {
+        lines.extend(render_group(&dones, theme, &blocked));
+    }
+
+    let mut out = lines.join("\n");
+    out.push('\n');
+    out
+}
+
+/// One tab separated line per task for scripts: id, status, deadline or `-`, name.
+/// Grouped like `list`, overdue first and closed last.
+pub fn render_oneline(list: &List) -> String {
+    let mut out = String::new();
+    for status in [Status::Overdue, Status::InProgress, Status::Todo, Status::Done] {
+        for el in list.with_status(status) {
+            let deadline = el.deadline.map(|d| d.format("%Y-%m-%dT%H:%M").to_string()).unwrap_or(String::from("-"));
+            out.push_str(&format!("{}\t{}\t{}\t{}\n", el.id, el.status.name(), deadline, el.get_name()));
+        }
+    }
+    out
+}
+
+/// Print all tasks, cutting lines that are wider than the terminal
+pub fn list_tasks(list: &List, theme: &Theme, sort: SortKey, hide_done: bool) {
+    use std::io::IsTerminal;
+
+    let out = render_tasks(list, theme, sort, hide_done);
+    match terminal_columns().filter(|_| std::io::stdout().is_terminal()) {
+        Some(columns) => {
+            let lines = out.lines().map(|l| truncate_display(l, columns)).collect::<Vec<String>>();
+            page_or_print(&format!("{}\n", lines.join("\n")));
+        },
+        None => page_or_print(&out)
+    }
+}
+
+/// Cut text to at most `max` terminal cells, ending it with an ellipsis when shortened
+pub fn truncate_display(name: &str, max: usize) -> String {
+    if name.width() <= max {
+        return name.to_string()
+    }
+
+    let mut out = String::new();
+    let mut width = 0;
+    for c in name.chars() {
+        let w = unicode_width::UnicodeWidthChar::width(c).unwrap_or(0);
+        if width + w + 1 > max {
+            break
+        }
+        out.push(c);
+        width += w;
+    }
+    if max > 0 {
+        out.push('…');
+    }
+    out
+}
+
+/// Page output only on a terminal, when it doesn't fit and paging isn't disabled
+pub fn should_page(is_tty: bool, lines: usize, rows: Option<usize>, no_pager: bool) -> bool {
+    match rows {
+        Some(rows) => is_tty && !no_pager && lines > rows,
+        None => false
+    }
+}
+
+/// Width of the terminal, from `COLUMNS` or `tput`
+fn terminal_columns() -> Option<usize> {
+    if let Some(columns) = std::env::var("COLUMNS").ok().and_then(|c| c.parse::<usize>().ok()) {
+        return Some(columns)
+    }
+
+    let output = Command::new("tput").arg("cols").stderr(std::process::Stdio::null()).output().ok()?;
+    String::from_utf8_lossy(&output.stdout).trim().parse::<usize>().ok()
+}
+
+/// Height of the terminal, from `LINES` or `tput`
+fn terminal_rows() -> Option<usize> {
+    if let Some(rows) = std::env::var("LINES").ok().and_then(|l| l.parse::<usize>().ok()) {
+        return Some(rows)
+    }
+
+    let output = Command::new("tput").arg("lines").stderr(std::process::Stdio::null()).output().ok()?;
+    String::from_utf8_lossy(&output.stdout).trim().parse::<usize>().ok()
+}
+
+/// Send output through `$PAGER`, default `less`, when it is longer than the terminal, else print it
+pub fn page_or_print(output: &str) {
+    use std::io::IsTerminal;
+
+    let no_pager = std::env::var("TODO_NO_PAGER").is_ok_and(|v| v == "1");
+    let is_tty = std::io::stdout().is_terminal();
+    if !should_page(is_tty, output.lines().count(), terminal_rows().filter(|_| is_tty), no_pager) {
+        print!("{}", output);
+        return
+    }
+
+    let pager = std::env::var("PAGER").ok().filter(|p| !p.trim().is_empty()).unwrap_or(String::from("less"));
+    let mut parts = pager.split_whitespace();
+    let spawned = Command::new(parts.next().unwrap_or("less"))
+        .args(parts)
+        .stdin(std::process::Stdio::piped())
+        .spawn();
+
+    match spawned {
+        Ok(mut child) => {
+            if let Some(mut stdin) = child.stdin.take() {
+                // The pager may quit before reading everything
+                let _ = stdin.write_all(output.as_bytes());
+            }
+            let _ = child.wait();
+        },
+        Err(_) => print!("{}", output)
+    }
+}
+
+/// The one task to work on next: the earliest deadline, or else the oldest of the most urgent open tasks
+pub fn focus_task(list: &List) -> Option<&Entry> {
+    list.next_deadline().or_else(|| {
+        list.pending().into_iter()
+            .min_by_key(|e| (std::cmp::Reverse(e.priority), e.timestamp, e.id))
+    })
+}
+
+/// Print a single task on its own, with nothing else around it, see `Entry::remaining_display`
+pub fn show_focus(entry: &Entry, day_start_hour: u32) {
+    println!();
+    println!("    >> {} <<", entry.get_name());
+    if entry.deadline.is_some() {
+        println!("       {}", entry.remaining_display(day_start_hour));
+    }
+    println!();
+}
+
+/// Pad text with spaces to `width` terminal cells, wide characters count as two
+pub fn pad_to_width(text: &str, width: usize) -> String {
+    let padding = width.saturating_sub(text.width());
+    format!("{}{}", text, " ".repeat(padding))
+}
+
+/// Print the last `n` added and closed tasks
+pub fn show_recent(list: &List, theme: &Theme, n: usize) {
+    let now = Local::now().naive_local();
+
+    println!("Recently added:");
+    for el in list.recent_created(n) {
+        println!("  {} ({})", el.render(theme), humanize_ago(el.timestamp, now));
+    }
+    println!("Recently closed:");
+    for el in list.recent_closed(n) {
+        if let Some(completed_at) = el.completed_at {
+            println!("  {} ({})", el.render(theme), humanize_ago(completed_at, now));
+        }
+    }
+}
+
+/// Print open tasks of one priority, earliest deadline first
+pub fn list_by_priority(list: &List, theme: &Theme, priority: Priority) {
+    let matching = list.filter_by_priority(priority);
+
+    if matching.is_empty() {
+        println!("You have no open {:?} priority tasks", priority);
+        return
+    }
+
+    for el in matching {
+        println!("{}", el.render(theme));
+    }
+}
+
+/// Print the Someday/Maybe section
+pub fn list_someday(list: &List, theme: &Theme) {
+    let someday = list.someday();
+
+    if someday.is_empty() {
+        println!("Someday/Maybe is empty");
+        return
+    }
+
+    println!("Someday/Maybe");
+    for el in someday {
+        println!("{}", el.render(theme));
+    }
+}
+
+pub fn list_tag_counts(list: &List) {
+    let mut counts = list.count_by_tag().into_iter().collect::<Vec<(String, usize)>>();
+
+    if counts.is_empty() {
+        println!("You have no open tasks");
+        return
+    }
+
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    let width = counts.iter().map(|(tag, _)| tag.width()).max().unwrap_or(0);
+    for (tag, count) in counts {
+        println!("{} {}", pad_to_width(&tag, width), count);
+    }
+}
+
+/// Help text for a single command
+pub struct CommandHelp {
+    pub name: &'static str,
+    pub usage: &'static str,
+    pub summary: &'static str,
+    pub details: &'static str
+}
+
+/// Every command understood by the interactive loop
+pub const COMMANDS: &[CommandHelp] = &[
+    CommandHelp {
+        name: "add",
+        usage: "add [task_name]",
+        summary: "Adds new task named [task_name] under TODO.",
+        details: "You are then asked for a deadline: YYYY-MM-DD (or with / or . separators),
+        an offset like +3d or +2w, a weekday like fri or next monday or a Unix time like @1704067200.
+        Leave it empty for a task without deadline. Quote the name to keep it literal.
+        Example: add Renew passport, add \"2024 planning\""
+    },
+    CommandHelp {
+        name: "list",
+        usage: "list [sort priority|deadline|created|name|insertion] | list priority low|normal|high | list someday | list --oneline",
+        summary: "List all overdue, in progress, todo and closed tasks, in that order.",
+        details: "Each group is sorted by the given key, or by the default_sort config when omitted.
+        Overdue tasks always come longest overdue first and closed tasks most recently closed first.
+        With priority, only open tasks of that priority are listed, earliest deadline first.
+        Low priority todo tasks without a deadline are only listed with someday.
+        Lines wider than the terminal are cut short, show prints the full name.
+        With --oneline, each task is printed as id, status, deadline (or -) and name separated by tabs.
+        Example: list sort deadline"
+    },
+    CommandHelp {
+        name: "priority",
+        usage: "priority [task_id] low|normal|high",
+        summary: "Sets how urgent a task is.",
+        details: "Tasks are normal priority when added."
+    },
+    CommandHelp {
+        name: "close",
+        usage: "close [--force] [--quiet] [task_id|task_name|#tag] [: note]",
+        summary: "Close task with provided [task_id], moves it from TODO to done.",
+        details: "A name closes the only open task with that name, ignoring case.
+        With --force, closing an already closed task is not an error. The remaining todo and overdue counts are shown after, unless --quiet is given.
+        A #tag closes every open task with that tag. Text after a colon is kept as a comment on the task.
+        Examples: close 3, close buy milk, close --force 3, close #meeting, close 3 : fixed upstream"
+    },
+    CommandHelp {
+        name: "start",
+        usage: "start [task_id]",
+        summary: "Marks a todo or overdue task as in progress.",
+        details: "In progress tasks are listed after overdue ones and are closed as usual.
+        Example: start 3"
+    },
+    CommandHelp {
+        name: "report",
+        usage: "report month [YYYY-MM]",
+        summary: "Shows how many tasks were added and closed in a month.",
+        details: "Defaults to the current month. Net is positive when more tasks were added than closed.
+        Example: report month 2024-03"
+    },
+    CommandHelp {
+        name: "deadline",
+        usage: "deadline [task_id] [deadline|none]",
+        summary: "Changes or removes the deadline of a task.",
+        details: "The deadline takes the same formats as add, none or clear removes it. An overdue task without deadline goes back to todo.
+        Examples: deadline 3 +2d, deadline 3 none"
+    },
+    CommandHelp {
+        name: "shift",
+        usage: "shift [#tag] [+N|-N][m|h|d|w]",
+        summary: "Moves the deadlines of the open tasks carrying a tag.",
+        details: "Tasks without a deadline are left alone, statuses are updated afterwards.
+        Example: shift #q3 +7d"
+    },
+    CommandHelp {
+        name: "bump",
+        usage: "bump [deadline]",
+        summary: "Moves the deadline of every overdue task.",
+        details: "The deadline takes the same formats as add, the tasks go back to todo.
+        Example: bump +3d"
+    },
+    CommandHelp {
+        name: "block",
+        usage: "block [task_id] needs [other_id]",
+        summary: "Marks a task as waiting on another one.",
+        details: "The task is shown as blocked in list until the other one is closed.
+        Example: block 4 needs 2"
+    },
+    CommandHelp {
+        name: "recent",
+        usage: "recent [n]",
+        summary: "Shows the last tasks added and closed.",
+        details: "Shows 5 of each unless a count is given."
+    },
+    CommandHelp {
+        name: "share",
+        usage: "share [task_id]",
+        summary: "Prints a token to copy a task elsewhere.",
+        details: "The token holds the name, deadline, tags and priority, use import token on the other side."
+    },
+    CommandHelp {
+        name: "import",
+        usage: "import token [token]",
+        summary: "Adds a task from a token printed by share.",
+        details: "The task gets the next free id."
+    },
+    CommandHelp {
+        name: "dedup",
+        usage: "dedup",
+        summary: "Merges open tasks that have the same name.",
+        details: "Names are compared ignoring case. The task with the earliest deadline is kept, or the oldest one
+        when none has a deadline. The tasks to remove are shown first and you are asked to confirm."
+    },
+    CommandHelp {
+        name: "template",
+        usage: "template [path]",
+        summary: "Adds the tasks of a template file.",
+        details: "The template is a JSON array of {name, deadline_offset_days, priority, tags} objects, deadlines are counted in days from now."
+    },
+    CommandHelp {
+        name: "archive",
+        usage: "archive",
+        summary: "Moves closed tasks to the archive file.",
+        details: "The archive sits next to the task file, e.g. tasks.archive.jsonl, and each run adds to the end of it."
+    },
+    CommandHelp {
+        name: "clean",
+        usage: "clean",
+        summary: "Fixes overdue markers that don't match the deadlines.",
+        details: "Todo tasks past their deadline become overdue, overdue tasks with a later deadline go back to todo."
+    },
+    CommandHelp {
+        name: "menu",
+        usage: "menu",
+        summary: "Lists open tasks by number and closes the one picked.",
+        details: "Numbers run from 1 and are not task ids. An invalid choice is asked again once, then nothing is closed."
+    },
+    CommandHelp {
+        name: "focus",
+        usage: "focus",
+        summary: "Shows only the next task to work on.",
+        details: "That is the task with the earliest deadline, or the oldest of the highest priority ones.
+        Type done to close it and move to the next one, an empty line leaves focus mode."
+    },
+    CommandHelp {
+        name: "random",
+        usage: "random",
+        summary: "Picks an open task to work on.",
+        details: "Every open task is equally likely to be picked."
+    },
+    CommandHelp {
+        name: "context",
+        usage: "context",
+        summary: "Prints the task file, list defaults and open counts on one line.",
+        details: "Meant for shell prompts, e.g. file=tasks.json sort=priority hide_done=false open=3 overdue=1"
+    },
+    CommandHelp {
+        name: "summary",
+        usage: "summary",
+        summary: "Shows how many tasks are open and overdue.",
+        details: "The same line is shown when the app starts."
+    },
+    CommandHelp {
+        name: "between",
+        usage: "between [start] [end]",
+        summary: "Lists open tasks due between two dates, both included.",
+        details: "Dates take the same formats as add deadlines.
+        Example: between 2024-03-01 2024-03-31"
+    },
+    CommandHelp {
+        name: "week",
+        usage: "week",
+        summary: "Lists open tasks due this week.",
+        details: "The week starts on monday by default, set week_start to sunday or rolling to change it."
+    },
+    CommandHelp {
+        name: "forecast",
+        usage: "forecast [weeks]",
+        summary: "Charts how many open tasks are due each week.",
+        details: "Covers 4 weeks from the current one unless a count is given. Overdue tasks and tasks without a deadline are not counted."
+    },
+    CommandHelp {
+        name: "habits",
+        usage: "habits",
+        summary: "Charts how many tasks were added on each weekday.",
+        details: "Counts every task, open or closed, by the day it was created."
+    },
+    CommandHelp {
+        name: "debt",
+        usage: "debt",
+        summary: "Shows how far behind overdue tasks are in total.",
+        details: "Sums, across overdue tasks, the time since each deadline passed."
+    },
+    CommandHelp {
+        name: "show",
+        usage: "show [task_id]",
+        summary: "Show all details of a task.",
+        details: "Includes creation time, deadline and time left until it, tags and link.
+        Example: show 3"
+    },
+    CommandHelp {
+        name: "dup",
+        usage: "dup [task_id]",
+        summary: "Adds a copy of a task under TODO.",
+        details: "The copy keeps the name, deadline and tags but gets a new id.
+        Example: dup 3"
+    },
+    CommandHelp {
+        name: "progress",
+        usage: "progress [task_id] [percent]",
+        summary: "Records how much of a task is done.",
+        details: "Values are clamped to 0-100 and shown as a bar by show. At 100 you are asked to close the task.
+        Without arguments, shows how many of all tasks are closed.
+        Example: progress 3 40"
+    },
+    CommandHelp {
+        name: "rename",
+        usage: "rename [task_id] [task_name]",
+        summary: "Changes the name of a task.",
+        details: "Quotes around the new name are dropped, as with add.
+        Example: rename 3 \"Pack books\""
+    },
+    CommandHelp {
+        name: "maybe",
+        usage: "maybe [task_name]",
+        summary: "Adds new task named [task_name] to Someday/Maybe.",
+        details: "The task gets low priority and no deadline, so list leaves it out until it is given either.
+        Example: maybe Learn the cello"
+    },
+    CommandHelp {
+        name: "remind",
+        usage: "remind [task_id] [duration|none]",
+        summary: "Flags a task at startup once its deadline is this close.",
+        details: "Durations are a number of minutes, hours, days or weeks, e.g. 30m, 2h, 1d or 1w.
+        Example: remind 4 2h"
+    },
+    CommandHelp {
+        name: "repeat",
+        usage: "repeat [task_id] daily|weekly|none",
+        summary: "Makes a task come back every day or week.",
+        details: "A missed recurring task isn't marked overdue, its deadline moves to the next occurrence instead.
+        Example: repeat 2 daily"
+    },
+    CommandHelp {
+        name: "comment",
+        usage: "comment [task_id] [text]",
+        summary: "Adds a timestamped note to a task.",
+        details: "Notes are kept in the order they were written and listed by show.
+        Example: comment 3 Waiting on the landlord"
+    },
+    CommandHelp {
+        name: "subtask",
+        usage: "subtask [parent_id] [task_name]",
+        summary: "Adds new task named [task_name] under task [parent_id].",
+        details: "Subtasks are listed indented under their parent.
+        Example: subtask 3 Pack books"
+    },
+    CommandHelp {
+        name: "tag",
+        usage: "tag [task_id] [tags]",
+        summary: "Adds the space separated [tags] to a task.",
+        details: "A leading '#' is optional and tags a task already has are ignored.
+        Example: tag 3 work urgent"
+    },
+    CommandHelp {
+        name: "tags",
+        usage: "tags",
+        summary: "Count open tasks per tag, most used first.",
+        details: "Tasks without tags are counted under (untagged)."
+    },
+    CommandHelp {
+        name: "retag",
+        usage: "retag [old_tag] [new_tag]",
+        summary: "Renames a tag on every task.",
+        details: "Matching ignores case, tasks that already have [new_tag] keep a single copy.
+        Example: retag @work @office"
+    },
+    CommandHelp {
+        name: "link",
+        usage: "link [task_id] [url]",
+        summary: "Attaches an http(s) [url] to a task.",
+        details: "Replaces any link the task already has.
+        Example: link 3 https://example.com/ticket/42"
+    },
+    CommandHelp {
+        name: "open",
+        usage: "open [task_id]",
+        summary: "Opens the task's link in the browser.",
+        details: "Example: open 3"
+    },
+    CommandHelp {
+        name: "export",
+        usage: "export [json|csv|markdown] [path]",
+        summary: "Writes all tasks to [path] in the given format.",
+        details: "The task file itself is saved on exit as JSON, or as TOML or YAML when TODO_FORMAT
+        says so or its name ends in .toml, .yaml or .yml.
+        Example: export csv tasks.csv"
+    },
+    CommandHelp {
+        name: "remove",
+        usage: "remove [task_id]",
+        summary: "Removes task from list. Other task ids are not affected.",
+        details: "Example: remove 3"
+    },
+    CommandHelp {
+        name: "add-many",
+        usage: "add-many",
+        summary: "Adds one task per line until an empty line.",
+        details: "No deadline is asked for, useful for pasting a list."
+    },
+    CommandHelp {
+        name: "help",
+        usage: "help [command]",
+        summary: "Show this message, or detailed help for [command].",
+        details: "Example: help close"
+    },
+    CommandHelp {
+        name: "quit",
+        usage: "quit",
+        summary: "Exit TODO cli.",
+        details: "Changes are saved to the task file on exit."
+    },
+];
+
+/// Detailed help for a single command
+pub fn command_help(name: &str) -> Option<String> {
+    COMMANDS.iter()
+        .find(|c| c.name == name)
+        .map(|c| format!("    {}\n        {}\n        {}", c.usage, c.summary, c.details))
+}
+
+pub fn show_help() {
+    let mut help_string = String::from("\n    Usage:\n");
+    for c in COMMANDS.iter() {
+        help_string.push_str(&format!("    {}\n        {}\n\n", c.usage, c.summary));
+    }
+    println!("{}", help_string);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::List;
+
+    #[test]
+    fn list_size() {
+        let mut list = List::new();
+        let total_tasks = 100;
+        for i in 0..total_tasks {
+            let curr_task_name = format!("Sample task {}", i);
+            list.add_task(&curr_task_name, None).unwrap();
+        }
+        assert_eq!(list.get_size(), total_tasks);
+    }
+
+    #[test]
+    fn new_task() {
+        let mut list = List::new();
+        let to_close = list.add_task("Sample task", None).unwrap();
+
+        assert_eq!(list.get_all()[to_close as usize].status, Status::Todo);
+        list.close_task(to_close).unwrap();
+        assert_eq!(list.get_all()[to_close as usize].status, Status::Done);
+    }
+
+    #[test]
+    fn add_returns_ids() {
+        let mut list = List::new();
+
+        for expected in 0..5 {
+            let id = list.add_task(&format!("Task {}", expected), None).unwrap();
+            assert_eq!(id, expected);
+            assert_eq!(list.get_cursor(), id + 1);
+        }
+        assert_eq!(list.add_task("  ", None), Err(TaskError::EmptyName));
+        assert_eq!(list.get_size(), 5);
+    }
+
+    #[test]
+    fn parse_pass() {
+        let line = String::from("2024-01-01\n");
+        let res = parse_deadline(line, 0).unwrap();
+        let exp = Local.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap().naive_local();
+
+        assert_eq!(res, exp);
+    }
+
+    #[test]
+    fn parse_slash_and_dot() {
+        let exp = Local.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap().naive_local();
+
+        assert_eq!(parse_deadline(String::from("2024/01/01\n"), 0), Some(exp));
+        assert_eq!(parse_deadline(String::from("2024.01.01"), 0), Some(exp));
+    }
+
+    #[test]
+    fn parse_fail_mixed_separators() {
+        assert!(parse_deadline(String::from("2024/01-01"), 0).is_none());
+        assert!(parse_deadline(String::from("2024.01/01"), 0).is_none());
+    }
+
+    #[test]
+    fn parse_fail_not_date() {
+        let line_error = String::from("2024-01-011232");
+        let res = parse_deadline(line_error, 0);
+        assert!(res.is_none());
+    }
+
+    #[test]
+    fn parse_fail_empty() {
+        let line_error = String::from("");
+        let res = parse_deadline(line_error, 0);
+        assert!(res.is_none());
+    }
+
+    #[test]
+    fn parse_relative_pass() {
+        let now = Local.with_ymd_and_hms(2024, 1, 1, 9, 30, 0).unwrap().naive_local();
+        let res = parse_relative("+3d", now).unwrap();
+        let exp = Local.with_ymd_and_hms(2024, 1, 4, 9, 30, 0).unwrap().naive_local();
+
+        assert_eq!(res, exp);
+        assert_eq!(parse_relative("+2w", now), Some(now + Duration::days(14)));
+    }
+
+    #[test]
+    fn parse_relative_overflow() {
+        let now = Local::now().naive_local();
+
+        assert!(parse_relative("+999999999d", now).is_none());
+        assert!(parse_relative("+99999999999999999999d", now).is_none());
+        assert!(parse_relative("+1d", NaiveDateTime::MAX).is_none());
+        assert_eq!(parse_offset("+999999999w"), Err(TaskError::OffsetTooLong(MAX_RELATIVE_DAYS)));
+        assert_eq!(parse_offset("+99999999999999999999d"), Err(TaskError::OffsetTooLong(MAX_RELATIVE_DAYS)));
+        assert!(matches!(parse_offset("+"), Err(TaskError::InvalidOffset(_))));
+        assert!(matches!(parse_offset("+-3d"), Err(TaskError::InvalidOffset(_))));
+        assert!(matches!(parse_offset("++3d"), Err(TaskError::InvalidOffset(_))));
+        assert!(parse_deadline(String::from("+999999999d"), 0).is_none());
+    }
+
+    #[test]
+    fn parse_weekday_upcoming() {
+        // A Wednesday
+        let today = NaiveDate::from_ymd_opt(2024, 1, 3).unwrap();
+        let friday = NaiveDate::from_ymd_opt(2024, 1, 5).unwrap().and_hms_opt(0, 0, 0);
+
+        assert_eq!(parse_weekday("friday", today), friday);
+        assert_eq!(parse_weekday("Fri", today), friday);
+        assert_eq!(parse_weekday("wednesday", today), NaiveDate::from_ymd_opt(2024, 1, 10).unwrap().and_hms_opt(0, 0, 0));
+    }
+
+    #[test]
+    fn parse_weekday_next() {
+        let today = NaiveDate::from_ymd_opt(2024, 1, 3).unwrap();
+
+        assert_eq!(parse_weekday("next monday", today), NaiveDate::from_ymd_opt(2024, 1, 15).unwrap().and_hms_opt(0, 0, 0));
+        assert_eq!(parse_weekday("NEXT thu", today), NaiveDate::from_ymd_opt(2024, 1, 11).unwrap().and_hms_opt(0, 0, 0));
+    }
+
+    #[test]
+    fn parse_weekday_invalid() {
+        let today = NaiveDate::from_ymd_opt(2024, 1, 3).unwrap();
+
+        assert!(parse_weekday("someday", today).is_none());
+        assert!(parse_weekday("next", today).is_none());
+        assert!(parse_deadline(String::from("blursday\n"), 0).is_none());
+    }
+
+    #[test]
+    fn parse_epoch_prefixed() {
+        let expected = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap().and_hms_opt(0, 0, 0);
+        assert_eq!(parse_epoch("@1704067200", &chrono::Utc), expected);
+        assert_eq!(parse_epoch("@1704067200", &chrono::FixedOffset::east_opt(3600).unwrap()), expected.map(|d| d + Duration::hours(1)));
+        assert!(parse_epoch("@99999999999999999", &chrono::Utc).is_none());
+        assert!(parse_epoch("@-5", &chrono::Utc).is_none());
+        assert!(parse_deadline(String::from("@1704067200\n"), 0).is_some());
+    }
+
+    #[test]
+    fn parse_duration_units() {
+        assert_eq!(parse_duration("2h"), Some(Duration::hours(2)));
+        assert_eq!(parse_duration("1d"), Some(Duration::days(1)));
+        assert_eq!(parse_duration(" 30m "), Some(Duration::minutes(30)));
+        assert_eq!(parse_duration("1w"), Some(Duration::weeks(1)));
+        assert_eq!(parse_duration("soon"), None);
+        assert_eq!(parse_duration("h"), None);
+        assert_eq!(parse_duration("-2h"), None);
+        assert_eq!(parse_duration("2x"), None);
+        assert_eq!(parse_duration("99999999w"), None);
+    }
+
+    #[test]
+    fn remind_window() {
+        let mut list = List::new();
+        let id = list.add_task("Submit report", Some(reference_now() + Duration::hours(1))).unwrap();
+        let silent = list.add_task("No reminder", Some(reference_now() + Duration::minutes(30))).unwrap();
+        list.set_reminder(id, parse_duration("2h")).unwrap();
+
+        let entry = list.find(id).unwrap();
+        assert!(!entry.should_remind_at(reference_now() - Duration::hours(2), 0));
+        assert!(entry.should_remind_at(reference_now() - Duration::hours(1), 0));
+        assert!(entry.should_remind_at(reference_now(), 0));
+        assert!(!entry.should_remind_at(reference_now() + Duration::hours(1), 0));
+        assert!(!list.find(silent).unwrap().should_remind_at(reference_now(), 0));
+        assert_eq!(list.reminders_at(reference_now()).len(), 1);
+
+        list.close_task(id).unwrap();
+        assert!(list.reminders_at(reference_now()).is_empty());
+    }
+
+    #[test]
+    fn remind_lead_overflow() {
+        let mut list = List::new();
+        let id = list.add_task("Far lead", Some(reference_now() + Duration::hours(1))).unwrap();
+        list.find_mut(id).unwrap().remind_before = Some(Duration::weeks(99999999));
+
+        assert!(list.find(id).unwrap().should_remind_at(reference_now(), 0));
+        assert_eq!(list.reminders_at(reference_now()).len(), 1);
+    }
+
+    #[test]
+    fn parse_year_range() {
+        assert_eq!(parse_deadline(String::from("2031-05-06"), 0), NaiveDate::from_ymd_opt(2031, 5, 6).unwrap().and_hms_opt(0, 0, 0));
+        assert!(parse_deadline(String::from("20244-01-01"), 0).is_none());
+        assert!(parse_deadline(String::from("1969-12-31"), 0).is_none());
+        assert!(parse_deadline(String::from("+99999w"), 0).is_none());
+    }
+
+    #[test]
+    fn parse_bare_number_rejected() {
+        assert!(parse_deadline(String::from("1704067200"), 0).is_none());
+        assert!(parse_deadline(String::from("2024"), 0).is_none());
+        assert!(parse_epoch("1704067200", &chrono::Utc).is_none());
+    }
+
+    #[test]
+    fn overdue_true() {
+        let deadline = parse_deadline(String::from("2000-01-01"), 0);
+        let entry = Entry::new(0, String::from("test entry"), deadline);
+
+        assert!(entry.is_overdue(0));
+    }
+
+    #[test]
+    fn overdue_missing() {
+        // Empty deadline, results in null
+        let deadline = parse_deadline(String::from(""), 0);
+        let entry = Entry::new(0, String::from("test entry"), deadline);
+
+        assert!(!entry.is_overdue(0));
+    }
+
+    #[test]
+    fn not_overdue() {
+        // One day from now
+        let curr_time = Local::now().naive_local() + std::time::Duration::from_secs(60*60*24);
+        let deadline_str = format!("{}-{}-{}", curr_time.year(), curr_time.month(), curr_time.day());
+        let deadline = parse_deadline(deadline_str, 0);
+        let entry = Entry::new(0, String::from("test entry"), deadline);
+
+        assert!(!entry.is_overdue(0));
+    }
+
+    #[test]
+    fn render_theme() {
+        let theme = Theme::from_json(r#"{"overdue": "!", "todo": "o", "done": "x"}"#).unwrap();
+        let mut entry = Entry::new(3, String::from("Themed task\n"), None);
+
+        assert_eq!(entry.render(&theme), "o 3 Themed task");
+        entry.status = Status::Overdue;
+        assert_eq!(entry.render(&theme), "! 3 Themed task");
+        entry.status = Status::Done;
+        assert_eq!(entry.render(&theme), "x 3 Themed task");
+    }
+
+    #[test]
+    fn tag_counts() {
+        let mut list = List::new();
+        list.add_task("Write report", None).unwrap();
+        list.add_task("Review PR", None).unwrap();
+        list.add_task("Groceries", None).unwrap();
+        list.add_task("Old meeting", None).unwrap();
+
+        list.tag_task(0, &["#work", "writing"]).unwrap();
+        list.tag_task(1, &["work", "WORK"]).unwrap();
+        list.tag_task(3, &["work"]).unwrap();
+        list.close_task(3).unwrap();
+
+        let counts = list.count_by_tag();
+
+        assert_eq!(list.get_all()[1].tags, vec![String::from("work")]);
+        assert_eq!(counts.len(), 3);
+        assert_eq!(counts["work"], 2);
+        assert_eq!(counts["writing"], 1);
+        assert_eq!(counts["(untagged)"], 1);
+    }
+
+    #[test]
+    fn merge_three_duplicates() {
+        let mut list = List::new();
+        let first = list.add_task("Call Bob", None).unwrap();
+        let dated = list.add_task(" call bob", Some(at(2030, 1, 1))).unwrap();
+        let third = list.add_task("CALL BOB", None).unwrap();
+        let closed = list.add_task("Call Bob", None).unwrap();
+        list.close_task(closed).unwrap();
+        let other = list.add_task("Call Alice", None).unwrap();
+        list.add_dependency(other, third).unwrap();
+
+        assert_eq!(list.duplicates(), vec![(first, dated), (third, dated)]);
+        assert_eq!(list.merge_duplicates(), 2);
+
+        let ids = list.get_all().iter().map(|e| e.get_id()).collect::<Vec<i32>>();
+        assert_eq!(ids, vec![dated, closed, other]);
+        assert_eq!(list.find(other).unwrap().get_blocked_by(), &vec![dated]);
+        assert_eq!(list.merge_duplicates(), 0);
+    }
+
+    #[test]
+    fn merge_drops_scattered_duplicate_blockers() {
+        let mut list = List::new();
+        let kept = list.add_task("Book flights", Some(at(2030, 1, 1))).unwrap();
+        let other = list.add_task("Pack", None).unwrap();
+        let dup = list.add_task("book flights", None).unwrap();
+        let waiting = list.add_task("Travel", None).unwrap();
+        for blocker in [kept, other, dup] {
+            list.add_dependency(waiting, blocker).unwrap();
+        }
+
+        assert_eq!(list.merge_duplicates(), 1);
+        assert_eq!(list.find(waiting).unwrap().get_blocked_by(), &vec![kept, other]);
+    }
+
+    #[test]
+    fn reading_keeps_recurring_within_grace() {
+        let due = Local::now().naive_local() - Duration::hours(2);
+        let mut list = List::new();
+        let id = list.add_task("Stretch", Some(due)).unwrap();
+        list.set_recurrence(id, Some(Recurrence::Daily)).unwrap();
+        let path = temp_path("recurring-grace.json");
+        export_with(list, &path, None).unwrap();
+
+        let mut list = read_or_create(&path).unwrap();
+        assert_eq!(list.find(id).unwrap().get_deadline(), Some(due));
+        list.check_overdues_after(Duration::hours(3));
+        assert_eq!(list.find(id).unwrap().get_deadline(), Some(due));
+        assert_eq!(list.find(id).unwrap().get_skipped(), 0);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn daily_task_nudged() {
+        let mut list = List::new();
+        let id = list.add_task("Water plants", Some(NaiveDate::from_ymd_opt(2024, 1, 7).unwrap().and_time(NaiveTime::MIN))).unwrap();
+        list.set_recurrence(id, Some(Recurrence::Daily)).unwrap();
+        let once = list.add_task("One-off", Some(at(2024, 1, 7))).unwrap();
+
+        assert_eq!(list.nudge_recurring(reference_now(), Duration::zero()), 1);
+        let entry = list.find(id).unwrap();
+        assert_eq!(entry.get_deadline(), NaiveDate::from_ymd_opt(2024, 1, 10).unwrap().and_hms_opt(0, 0, 0));
+        assert_eq!(entry.get_skipped(), 3);
+        assert_eq!(*entry.get_status(), Status::Todo);
+        assert_eq!(entry.get_completed_via(), None);
+        assert_eq!(list.find(once).unwrap().get_deadline(), Some(at(2024, 1, 7)));
+
+        assert_eq!(list.nudge_recurring(reference_now(), Duration::zero()), 0);
+        assert_eq!(list.find(id).unwrap().get_skipped(), 3);
+    }
+
+    #[test]
+    fn close_by_tag_only_tagged_open() {
+        let mut list = List::new();
+        for name in ["Standup", "Retro", "Write docs", "Old meeting"] {
+            list.add_task(name, None).unwrap();
+        }
+        list.tag_task(0, &["meeting"]).unwrap();
+        list.tag_task(1, &["#Meeting", "team"]).unwrap();
+        list.tag_task(3, &["meeting"]).unwrap();
+        list.close_task(3).unwrap();
+        let closed_before = list.find(3).unwrap().get_completed_at();
+
+        assert_eq!(list.close_by_tag("#MEETING"), 2);
+        let statuses = list.get_all().iter().map(|e| e.get_status().clone()).collect::<Vec<Status>>();
+        assert_eq!(statuses, vec![Status::Done, Status::Done, Status::Todo, Status::Done]);
+        assert_eq!(list.find(3).unwrap().get_completed_at(), closed_before);
+        assert_eq!(list.close_by_tag("meeting"), 0);
+    }
+
+    #[test]
+    fn comments_in_order() {
+        let mut list = List::new();
+        let id = list.add_task("Move out", None).unwrap();
+        list.add_comment(id, "Called the landlord").unwrap();
+        list.add_comment(id, " Deposit back next week ").unwrap();
+
+        let comments = list.find(id).unwrap().get_comments();
+        assert_eq!(comments.len(), 2);
+        assert_eq!(comments[0].1, "Called the landlord");
+        assert_eq!(comments[1].1, "Deposit back next week");
+        assert!(comments[0].0 <= comments[1].0);
+        assert!(comments[0].0 >= list.find(id).unwrap().get_timestamp());
+        assert_eq!(list.add_comment(9, "Nope"), Err(TaskError::NotFound(9)));
+
+        let old: Entry = serde_json::from_str(r#"{"id": 0, "task": "Old", "status": "Todo", "timestamp": "2024-01-02T09:00:00"}"#).unwrap();
+        assert!(old.get_comments().is_empty());
+    }
+
+    #[test]
+    fn deadline_sanity() {
+        let mut entry = Entry::new(0, String::from("Back-dated"), Some(at(2024, 1, 9)));
+        entry.timestamp = reference_now();
+        assert!(!entry.deadline_is_sane(0));
+
+        entry.set_deadline(Some(at(2024, 1, 11)));
+        assert!(entry.deadline_is_sane(0));
+        entry.set_deadline(NaiveDate::from_ymd_opt(2024, 1, 10).unwrap().and_hms_opt(0, 0, 0));
+        assert!(entry.deadline_is_sane(0));
+        entry.set_deadline(None);
+        assert!(entry.deadline_is_sane(0));
+
+        let mut list = List::new();
+        let past = parse_deadline(String::from("2000-01-01"), 0);
+        assert_eq!(list.add_task_checked("Late", past, true), Err(TaskError::DeadlineBeforeCreation));
+        assert_eq!(list.get_size(), 0);
+        assert!(list.add_task_checked("Late", past, false).is_ok());
+    }
+
+    #[test]
+    fn rename_returns_old_name() {
+        let mut list = List::new();
+        let id = list.add_task("Buy mlik", None).unwrap();
+
+        assert_eq!(list.rename_task(id, "Buy milk"), Ok(String::from("Buy mlik")));
+        assert_eq!(list.find(id).unwrap().get_name(), "Buy milk");
+        assert_eq!(list.rename_task(id, "  "), Err(TaskError::EmptyName));
+        assert_eq!(list.rename_task(9, "Other"), Err(TaskError::NotFound(9)));
+    }
+
+    #[test]
+    fn rename_tag_dedup() {
+        let mut list = List::new();
+        list.add_task("Standup", None).unwrap();
+        list.add_task("Expenses", None).unwrap();
+        list.add_task("Gym", None).unwrap();
+
+        list.tag_task(0, &["@work"]).unwrap();
+        list.tag_task(1, &["@Work", "@office", "admin"]).unwrap();
+        list.tag_task(2, &["@home"]).unwrap();
+
+        let changed = list.rename_tag("@WORK", "@office");
+
+        assert_eq!(changed, 2);
+        assert_eq!(list.get_all()[0].tags, vec![String::from("@office")]);
+        assert_eq!(list.get_all()[1].tags, vec![String::from("admin"), String::from("@office")]);
+        assert_eq!(list.get_all()[2].tags, vec![String::from("@home")]);
+    }
+
+    #[test]
+    fn list_diff() {
+        let mut before = List::new();
+        before.add_task("Kept", None).unwrap();
+        before.add_task("To close", None).unwrap();
+        before.add_task("To remove", None).unwrap();
+        before.add_task("Old name", None).unwrap();
+
+        let mut after = before.clone();
+        after.close_task(1).unwrap();
+        after.entries.retain(|e| e.id != 2);
+        after.entries_mut().nth(2).unwrap().task = String::from("New name");
+        after.add_task("Added", None).unwrap();
+
+        let changes = diff(&before, &after);
+
+        assert_eq!(changes, ListDiff {
+            added: vec![4],
+            closed: vec![1],
+            removed: vec![2],
+            renamed: vec![3]
+        });
+        assert!(diff(&before, &before).is_empty());
+    }
+
+    #[test]
+    fn link_validation() {
+        assert!(is_valid_link("https://example.com/ticket/1"));
+        assert!(is_valid_link("http://localhost:8080"));
+        assert!(!is_valid_link("ftp://example.com"));
+        assert!(!is_valid_link("example.com"));
+        assert!(!is_valid_link("https://"));
+        assert!(!is_valid_link("https://bad link"));
+    }
+
+    struct RecordingOpener(std::cell::RefCell<Vec<String>>);
+
+    impl Opener for RecordingOpener {
+        fn open(&self, link: &str) -> Result<(), Error> {
+            self.0.borrow_mut().push(link.to_string());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn open_task_link() {
+        let mut list = List::new();
+        list.add_task("Fix bug", None).unwrap();
+        list.add_task("No link", None).unwrap();
+
+        assert_eq!(list.set_link(0, "notalink"), Err(TaskError::InvalidLink(String::from("notalink"))));
+        list.set_link(0, "https://example.com/issue/7").unwrap();
+
+        let opener = RecordingOpener(std::cell::RefCell::new(Vec::new()));
+        open_link(&list, 0, &opener).unwrap();
+        assert!(open_link(&list, 1, &opener).is_err());
+
+        assert_eq!(*opener.0.borrow(), vec![String::from("https://example.com/issue/7")]);
+    }
+
+    #[test]
+    fn subtask_valid_parent() {
+        let mut list = List::new();
+        list.add_task("Move house", None).unwrap();
+        list.add_task("Unrelated", None).unwrap();
+
+        let child = list.add_subtask(0, "Pack books").unwrap();
+        let grandchild = list.add_subtask(child, "Buy boxes").unwrap();
+
+        assert_eq!(child, 2);
+        assert_eq!(grandchild, 3);
+        assert_eq!(list.get_all()[2].get_parent(), Some(0));
+        assert_eq!(list.get_all()[3].get_parent(), Some(2));
+
+        let lines = render_group(list.get_all(), &Theme::default(), &[]);
+        assert_eq!(lines, vec![
+            "| 0 Move house",
+            "  | 2 Pack books",
+            "    | 3 Buy boxes",
+            "| 1 Unrelated"
+        ]);
+    }
+
+    #[test]
+    fn subtask_missing_parent() {
+        let mut list = List::new();
+        list.add_task("Only task", None).unwrap();
+
+        assert_eq!(list.add_subtask(7, "Orphan"), Err(TaskError::NotFound(7)));
+        assert_eq!(list.get_size(), 1);
+    }
+
+    #[test]
+    fn sort_by_deadline() {
+        let mut list = List::new();
+        list.add_task("No deadline", None).unwrap();
+        list.add_task("Later", parse_deadline(String::from("2030-05-01"), 0)).unwrap();
+        list.add_task("Sooner", parse_deadline(String::from("2030-01-01"), 0)).unwrap();
+
+        let mut entries = list.get_all().clone();
+        SortKey::Deadline.sort(&mut entries);
+
+        let ids = entries.iter().map(|e| e.id).collect::<Vec<i32>>();
+        assert_eq!(ids, vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn close_twice_strict() {
+        let mut list = List::new();
+        list.add_task("Once", None).unwrap();
+
+        list.close_task(0).unwrap();
+        assert_eq!(list.close_task(0), Err(TaskError::NotFound(0)));
+    }
+
+    #[test]
+    fn close_twice_idempotent() {
+        let mut list = List::new();
+        list.add_task("Once", None).unwrap();
+
+        list.close_task_idempotent(0).unwrap();
+        list.close_task_idempotent(0).unwrap();
+        assert_eq!(list.get_all()[0].status, Status::Done);
+        assert_eq!(list.close_task_idempotent(5), Err(TaskError::NotFound(5)));
+    }
+
+    #[test]
+    fn humanize_durations() {
+        assert_eq!(humanize_remaining(None, false), "no deadline");
+        assert_eq!(humanize_remaining(Some(Duration::minutes(20)), false), "due soon");
+        assert_eq!(humanize_remaining(Some(Duration::hours(5)), false), "in 5h");
+        assert_eq!(humanize_remaining(Some(Duration::hours(51)), false), "in 2d 3h");
+        assert_eq!(humanize_remaining(Some(Duration::days(3)), false), "in 3d");
+        assert_eq!(humanize_remaining(Some(Duration::minutes(90)), false), "in 1h 30m");
+    }
+
+    #[test]
+    fn humanize_overdue() {
+        assert_eq!(humanize_remaining(Some(Duration::hours(5)), true), "overdue by 5h");
+        assert_eq!(humanize_remaining(Some(Duration::minutes(12)), true), "overdue by 12m");
+
+        let deadline = parse_deadline(String::from("2000-01-01"), 0);
+        let entry = Entry::new(0, String::from("Late"), deadline);
+        assert_eq!(entry.time_remaining(0), Some(Duration::zero()));
+        assert!(entry.remaining_display(0).starts_with("overdue by "));
+    }
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("todo-{}-{}", std::process::id(), name))
+            .to_string_lossy()
+            .to_string()
+    }
+
+    #[test]
+    fn lock_acquire_release() {
+        let fpath = temp_path("lock.json");
+
+        acquire_lock(&fpath).unwrap();
+        assert_eq!(acquire_lock(&fpath).unwrap_err().kind(), ErrorKind::AlreadyExists);
+
+        release_lock(&fpath).unwrap();
+        assert!(!Path::new(&lock_path(&fpath)).exists());
+        acquire_lock(&fpath).unwrap();
+        release_lock(&fpath).unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn lock_stale() {
+        let fpath = temp_path("stale.json");
+        fs::write(lock_path(&fpath), "4294967295").unwrap();
+
+        acquire_lock(&fpath).unwrap();
+        let owner = fs::read_to_string(lock_path(&fpath)).unwrap();
+        assert_eq!(owner, std::process::id().to_string());
+        release_lock(&fpath).unwrap();
+    }
+
+    #[test]
+    fn help_lookup() {
+        let help = command_help("close").unwrap();
+
+        assert!(help.contains("close [--force] [--quiet] [task_id|task_name|#tag]"));
+        assert!(help.contains("close buy milk"));
+        assert!(command_help("xyz").is_none());
+    }
+
+    #[test]
+    fn overdue_grace() {
+        let mut list = List::new();
+        let deadline = Local::now().naive_local() - Duration::hours(2);
+        list.add_task("Late", Some(deadline)).unwrap();
+        list.add_task("Closed", Some(deadline)).unwrap();
+        list.close_task(1).unwrap();
+
+        list.check_overdues_after(Duration::hours(3));
+        assert_eq!(list.get_all()[0].status, Status::Todo);
+
+        list.check_overdues();
+        assert_eq!(list.get_all()[0].status, Status::Overdue);
+        assert_eq!(list.get_all()[1].status, Status::Done);
+    }
+
+    #[test]
+    fn start_transition() {
+        let mut list = List::new();
+        list.add_task("Write draft", None).unwrap();
+        list.add_task("Late", parse_deadline(String::from("2000-01-01"), 0)).unwrap();
+        list.check_overdues();
+
+        list.start_task(0).unwrap();
+        list.start_task(1).unwrap();
+        assert_eq!(list.get_all()[0].status, Status::InProgress);
+        assert_eq!(list.get_all()[1].status, Status::InProgress);
+        assert_eq!(list.start_task(0), Err(TaskError::NotStartable(0)));
+
+        list.check_overdues();
+        assert_eq!(list.get_all()[1].status, Status::InProgress);
+
+        list.close_task(0).unwrap();
+        assert_eq!(list.get_all()[0].status, Status::Done);
+        assert_eq!(list.start_task(0), Err(TaskError::NotStartable(0)));
+    }
+
+    #[test]
+    fn status_in_progress_count() {
+        let mut list = List::new();
+        list.add_task("One", None).unwrap();
+        list.add_task("Two", None).unwrap();
+        list.add_task("Three", None).unwrap();
+        list.start_task(1).unwrap();
+        list.start_task(2).unwrap();
+
+        let res = list.get_status();
+
+        assert_eq!(res[&Status::InProgress], 2);
+        assert_eq!(res[&Status::Todo], 1);
+    }
+
+    #[test]
+    fn export_error_then_fallback() {
+        let mut list = List::new();
+        list.add_task("Survives", None).unwrap();
+        let name = format!("todo-{}-fallback.json", std::process::id());
+        let fpath = format!("/nonexistent-dir/{}", name);
+
+        assert!(export(list.clone(), &fpath).is_err());
+        let written = export_fallback(list, &fpath).unwrap();
+
+        assert_eq!(written, std::env::temp_dir().join(&name));
+        assert_eq!(load_list(&written.to_string_lossy(), None).unwrap().get_size(), 1);
+        fs::remove_file(written).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn export_fallback_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = temp_path("fallback-dir");
+        fs::create_dir_all(&dir).unwrap();
+        let name = format!("todo-{}-private-fallback.json", std::process::id());
+        let fpath = Path::new(&dir).join(&name).to_string_lossy().to_string();
+        fs::write(&fpath, "{}").unwrap();
+        fs::set_permissions(&fpath, fs::Permissions::from_mode(0o600)).unwrap();
+
+        let written = export_fallback(List::new(), &fpath).unwrap();
+        assert_eq!(written, std::env::temp_dir().join(&name));
+        assert_eq!(fs::metadata(&written).unwrap().permissions().mode() & 0o777, 0o600);
+        fs::remove_file(written).unwrap();
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn encrypted_round_trip() {
+        let mut list = List::new();
+        list.add_task("Secret task", None).unwrap();
+        let fpath = temp_path("encrypted.json");
+
+        export_with(list, &fpath, Some("hunter2")).unwrap();
+
+        let raw = fs::read(&fpath).unwrap();
+        assert!(crypto::is_encrypted(&raw));
+        assert!(!String::from_utf8_lossy(&raw).contains("Secret task"));
+
+        let loaded = load_list(&fpath, Some("hunter2")).unwrap();
+        assert_eq!(loaded.get_all()[0].get_name(), "Secret task");
+
+        assert_eq!(load_list(&fpath, Some("wrong")).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(load_list(&fpath, None).unwrap_err().kind(), ErrorKind::PermissionDenied);
+        fs::remove_file(fpath).unwrap();
+    }
+
+    #[test]
+    fn overdue_duration_sum() {
+        let now = Local.with_ymd_and_hms(2024, 3, 10, 12, 0, 0).unwrap().naive_local();
+        let mut list = List::new();
+        list.add_task("Two days late", Some(now - Duration::days(2))).unwrap();
+        list.add_task("Six hours late", Some(now - Duration::hours(6))).unwrap();
+        list.add_task("No deadline", None).unwrap();
+        list.add_task("Closed late", Some(now - Duration::days(9))).unwrap();
+        list.add_task("Upcoming", Some(now + Duration::days(1))).unwrap();
+        list.close_task(3).unwrap();
+        for entry in list.entries_mut().take(2) {
+            entry.status = Status::Overdue;
+        }
+
+        assert_eq!(list.overdue_duration_at(now), Duration::days(2) + Duration::hours(6));
+        assert_eq!(List::new().total_overdue_duration(), Duration::zero());
+    }
+
+    #[test]
+    fn duplicate_resets_state() {
+        let mut list = List::new();
+        let deadline = parse_deadline(String::from("2030-02-01"), 0);
+        let source = list.add_task("Water plants", deadline).unwrap();
+        list.tag_task(source, &["home"]).unwrap();
+        list.close_task(source).unwrap();
+        let old_timestamp = Local.with_ymd_and_hms(2020, 1, 1, 8, 0, 0).unwrap().naive_local();
+        list.entries_mut().next().unwrap().timestamp = old_timestamp;
+
+        let copy = list.duplicate_task(source).unwrap();
+        let entry = list.find(copy).unwrap();
+
+        assert_ne!(copy, source);
+        assert_eq!(entry.get_name(), "Water plants");
+        assert_eq!(entry.get_deadline(), deadline);
+        assert_eq!(entry.get_tags(), &vec![String::from("home")]);
+        assert_eq!(entry.status, Status::Todo);
+        assert_eq!(entry.get_completed_at(), None);
+        assert!(entry.get_timestamp() > old_timestamp);
+        assert!(list.find(source).unwrap().get_completed_at().is_some());
+        assert_eq!(list.duplicate_task(42), Err(TaskError::NotFound(42)));
+    }
+
+    #[test]
+    fn progress_clamped() {
+        let mut list = List::new();
+        let id = list.add_task("Thesis", None).unwrap();
+
+        assert_eq!(list.set_progress(id, 40), Ok(40));
+        assert_eq!(list.set_progress(id, 250), Ok(100));
+        assert_eq!(list.set_progress(id, -5), Ok(0));
+        assert_eq!(list.find(id).unwrap().get_progress(), Some(0));
+        assert_eq!(list.set_progress(9, 10), Err(TaskError::NotFound(9)));
+    }
+
+    #[test]
+    fn progress_bar_render() {
+        assert_eq!(progress_bar(0), "[----------] 0%");
+        assert_eq!(progress_bar(50), "[#####-----] 50%");
+        assert_eq!(progress_bar(100), "[##########] 100%");
+    }
+
+    #[test]
+    fn pending_excludes_done() {
+        let mut list = List::new();
+        list.add_task("Todo", None).unwrap();
+        list.add_task("Late", parse_deadline(String::from("2000-01-01"), 0)).unwrap();
+        list.add_task("Done", None).unwrap();
+        list.close_task(2).unwrap();
+        list.check_overdues();
+
+        let ids = list.pending().iter().map(|e| e.id).collect::<Vec<i32>>();
+
+        assert_eq!(ids, vec![0, 1]);
+        assert_eq!(list.get_all()[1].status, Status::Overdue);
+        assert_eq!(summary_line(&list), "1 todo / 1 overdue");
+    }
+
+    #[test]
+    fn context_for_prompt() {
+        let mut list = List::new();
+        list.add_task("Todo", None).unwrap();
+        list.add_task("Late", Some(at(2000, 1, 1))).unwrap();
+        list.add_task("Done", None).unwrap();
+        list.close_task(2).unwrap();
+        list.check_overdues();
+
+        let config = Config { file_path: String::from("work.json"), hide_done: true, ..Config::default() };
+        assert_eq!(context_line(&config, &list), "file=work.json sort=priority hide_done=true open=2 overdue=1");
+    }
+
+    #[test]
+    fn archive_accumulates() {
+        let path = temp_path("archive.jsonl");
+        let _ = fs::remove_file(&path);
+
+        let mut list = List::new();
+        for name in ["First", "Second", "Third", "Open"] {
+            list.add_task(name, None).unwrap();
+        }
+        list.close_task(0).unwrap();
+        assert_eq!(archive_done(&mut list, &path).unwrap(), 1);
+        assert_eq!(archive_done(&mut list, &path).unwrap(), 0);
+
+        list.close_task(1).unwrap();
+        list.close_task(2).unwrap();
+        assert_eq!(archive_done(&mut list, &path).unwrap(), 2);
+
+        let content = fs::read_to_string(&path).unwrap();
+        let archived = content.lines()
+            .map(|l| serde_json::from_str::<Entry>(l).unwrap().id)
+            .collect::<Vec<i32>>();
+        assert_eq!(archived, vec![0, 1, 2]);
+        assert_eq!(list.get_all().iter().map(|e| e.id).collect::<Vec<i32>>(), vec![3]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn archive_failure_keeps_tasks() {
+        let mut list = List::new();
+        list.add_task("Open", None).unwrap();
+        list.add_task("Done", None).unwrap();
+        list.close_task(1).unwrap();
+
+        assert!(archive_done(&mut list, "does/not/exist/tasks.archive.jsonl").is_err());
+        assert_eq!(list.get_size(), 2);
+        assert_eq!(archive_path("dir/work.json"), "dir/work.archive.jsonl");
+    }
+
+    fn reference_now() -> NaiveDateTime {
+        // A Wednesday
+        NaiveDate::from_ymd_opt(2024, 1, 10).unwrap().and_hms_opt(15, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn week_bounds_monday() {
+        let (start, end) = week_bounds(reference_now(), WeekMode::Monday, 0);
+        assert_eq!(start, NaiveDate::from_ymd_opt(2024, 1, 8).unwrap());
+        assert_eq!(end, NaiveDate::from_ymd_opt(2024, 1, 14).unwrap());
+    }
+
+    #[test]
+    fn week_bounds_sunday() {
+        let (start, end) = week_bounds(reference_now(), WeekMode::Sunday, 0);
+        assert_eq!(start, NaiveDate::from_ymd_opt(2024, 1, 7).unwrap());
+        assert_eq!(end, NaiveDate::from_ymd_opt(2024, 1, 13).unwrap());
+    }
+
+    #[test]
+    fn week_bounds_rolling() {
+        let (start, end) = week_bounds(reference_now(), WeekMode::Rolling, 0);
+        assert_eq!(start, NaiveDate::from_ymd_opt(2024, 1, 10).unwrap());
+        assert_eq!(end, NaiveDate::from_ymd_opt(2024, 1, 16).unwrap());
+    }
+
+    fn at(year: i32, month: u32, day: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap().and_hms_opt(12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn deadline_histogram_buckets() {
+        let mut list = List::new();
+        list.add_task("Thursday", Some(at(2024, 1, 11))).unwrap();
+        list.add_task("Sunday", Some(at(2024, 1, 14))).unwrap();
+        list.add_task("Next week", Some(at(2024, 1, 15))).unwrap();
+        list.add_task("Too far", Some(at(2024, 3, 1))).unwrap();
+        list.add_task("Earlier today", Some(at(2024, 1, 10))).unwrap();
+        list.add_task("All day today", Some(NaiveDate::from_ymd_opt(2024, 1, 10).unwrap().and_time(NaiveTime::MIN))).unwrap();
+        list.add_task("Someday", None).unwrap();
+
+        let histogram = list.deadline_histogram_at(reference_now(), 3);
+        assert_eq!(histogram, vec![
+            (NaiveDate::from_ymd_opt(2024, 1, 8).unwrap(), 3),
+            (NaiveDate::from_ymd_opt(2024, 1, 15).unwrap(), 1),
+            (NaiveDate::from_ymd_opt(2024, 1, 22).unwrap(), 0)
+        ]);
+        assert_eq!(forecast_chart(&histogram[..2]), "2024-01-08 ### 3\n2024-01-15 # 1");
+    }
+
+    #[test]
+    fn month_filters() {
+        let mut list = List::new();
+        for (i, (created, completed)) in [
+            (at(2024, 1, 31), Some(at(2024, 2, 1))),
+            (at(2024, 2, 10), None),
+            (at(2024, 2, 20), Some(at(2024, 2, 21))),
+            (at(2023, 2, 5), Some(at(2023, 2, 6)))
+        ].into_iter().enumerate() {
+            let mut entry = Entry::new(i as i32, format!("Task {}", i), None);
+            entry.timestamp = created;
+            entry.completed_at = completed;
+            list.push_entry(entry).unwrap();
+        }
+
+        let ids = |entries: Vec<&Entry>| entries.iter().map(|e| e.get_id()).collect::<Vec<i32>>();
+        assert_eq!(ids(list.created_in_month(2024, 2)), vec![1, 2]);
+        assert_eq!(ids(list.completed_in_month(2024, 2)), vec![0, 2]);
+        assert_eq!(ids(list.created_in_month(2024, 1)), vec![0]);
+        assert!(list.completed_in_month(2024, 1).is_empty());
+        assert_eq!(month_report(&list, 2024, 1), "2024-01: 1 created, 0 completed, net +1");
+
+        assert_eq!(parse_month("2024-03"), Some((2024, 3)));
+        assert!(parse_month("2024-13").is_none());
+        assert!(parse_month("march").is_none());
+    }
+
+    #[test]
+    fn focus_picks_single_task() {
+        let mut list = List::new();
+        let low = list.add_task("Old and unimportant", None).unwrap();
+        let high = list.add_task("Urgent", None).unwrap();
+        list.set_priority(low, Priority::Low).unwrap();
+        list.set_priority(high, Priority::High).unwrap();
+        list.add_task("Also normal", None).unwrap();
+        assert_eq!(focus_task(&list).unwrap().get_id(), high);
+
+        let due = list.add_task("Has a deadline", parse_deadline(String::from("2030-01-01"), 0)).unwrap();
+        let closed = list.add_task("Closed sooner", parse_deadline(String::from("2029-01-01"), 0)).unwrap();
+        list.close_task(closed).unwrap();
+        assert_eq!(focus_task(&list).unwrap().get_id(), due);
+
+        assert!(focus_task(&List::new()).is_none());
+    }
+
+    #[test]
+    fn pad_wide_characters() {
+        assert_eq!(pad_to_width("abc", 6), "abc   ");
+        // Each of these takes two cells
+        assert_eq!(pad_to_width("日本", 6), "日本  ");
+        assert_eq!(pad_to_width("日本", 6).width(), 6);
+        assert_eq!(pad_to_width("too long", 3), "too long");
+    }
+
+    #[test]
+    fn truncate_short_name() {
+        assert_eq!(truncate_display("Buy milk", 8), "Buy milk");
+        assert_eq!(truncate_display("", 0), "");
+    }
+
+    #[test]
+    fn truncate_long_name() {
+        let cut = truncate_display("Renew the passport before summer", 12);
+        assert_eq!(cut, "Renew the p…");
+        assert_eq!(cut.width(), 12);
+        assert_eq!(truncate_display("abc", 0), "");
+    }
+
+    #[test]
+    fn truncate_multibyte_name() {
+        assert_eq!(truncate_display("Café crème brûlée", 8), "Café cr…");
+        let cut = truncate_display("日本語のタスク", 7);
+        assert_eq!(cut, "日本語…");
+        assert!(cut.width() <= 7);
+    }
+
+    #[test]
+    fn recover_truncated_file() {
+        let mut list = List::new();
+        for name in ["First", "Second {with braces}", "Third"] {
+            list.add_task(name, None).unwrap();
+        }
+        let json = serde_json::to_string(&list).unwrap();
+        let cut = json.find("Third").unwrap();
+        let truncated = &json[..cut];
+        assert!(serde_json::from_str::<List>(truncated).is_err());
+
+        let recovered = recover_list(truncated);
+        assert_eq!(recovered.get_size(), 2);
+        assert_eq!(recovered.get_all()[1].get_name(), "Second {with braces}");
+        assert_eq!(recovered.get_cursor(), 2);
+
+        let path = temp_path("truncated.json");
+        fs::write(&path, truncated).unwrap();
+        assert_eq!(read_or_create(&path).unwrap().get_size(), 2);
+        assert_eq!(fs::read_to_string(corrupt_path(&path)).unwrap(), truncated);
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(corrupt_path(&path)).unwrap();
+
+        assert_eq!(recover_list("garbage").get_size(), 0);
+    }
+
+    #[test]
+    fn recent_created_order_and_limit() {
+        let mut list = List::new();
+        for (i, created) in [at(2024, 1, 1), at(2024, 3, 1), at(2024, 2, 1), at(2023, 1, 1)].into_iter().enumerate() {
+            let mut entry = Entry::new(i as i32, format!("Task {}", i), None);
+            entry.timestamp = created;
+            list.push_entry(entry).unwrap();
+        }
+
+        let ids = list.recent_created(3).iter().map(|e| e.get_id()).collect::<Vec<i32>>();
+        assert_eq!(ids, vec![1, 2, 0]);
+        assert_eq!(list.recent_created(10).len(), 4);
+        assert!(list.recent_closed(5).is_empty());
+
+        list.close_task(3).unwrap();
+        assert_eq!(list.recent_closed(5)[0].get_id(), 3);
+    }
+
+    #[test]
+    fn share_token_round_trip() {
+        let mut source = List::new();
+        let id = source.add_task("Book flights", parse_deadline(String::from("2030-04-01"), 0)).unwrap();
+        source.tag_task(id, &["travel"]).unwrap();
+        source.set_priority(id, Priority::High).unwrap();
+        let token = encode_task(source.find(id).unwrap());
+        assert!(!token.contains(char::is_whitespace));
+
+        let mut list = List::new();
+        list.add_task("Existing", None).unwrap();
+        let imported = list.import_token(&token).unwrap();
+        let entry = list.find(imported).unwrap();
+        assert_eq!(imported, 1);
+        assert_eq!(entry.get_name(), "Book flights");
+        assert_eq!(entry.get_deadline(), parse_deadline(String::from("2030-04-01"), 0));
+        assert_eq!(entry.get_tags(), &vec![String::from("travel")]);
+        assert_eq!(entry.get_priority(), Priority::High);
+
+        assert_eq!(decode_task("not a token!").unwrap_err(), TaskError::InvalidToken);
+    }
+
+    #[test]
+    fn blocked_until_closed() {
+        let mut list = List::new();
+        let first = list.add_task("Get keys", None).unwrap();
+        let second = list.add_task("Order parts", None).unwrap();
+        let task = list.add_task("Fix bike", None).unwrap();
+        list.add_dependency(task, first).unwrap();
+        list.add_dependency(task, second).unwrap();
+        assert_eq!(list.blocked_ids(), vec![task]);
+        assert!(render_tasks(&list, &Theme::default(), SortKey::Insertion, false).contains("| 2 Fix bike [blocked]"));
+
+        list.close_task(first).unwrap();
+        assert!(list.unblocked_by(first).is_empty());
+        list.close_task(second).unwrap();
+        assert_eq!(list.unblocked_by(second), vec![task]);
+        assert!(list.blocked_ids().is_empty());
+
+        let lines = render_tasks(&list, &Theme::default(), SortKey::Insertion, false);
+        assert!(!lines.contains("[blocked]"));
+    }
+
+    #[test]
+    fn dependency_cycle_rejected() {
+        let mut list = List::new();
+        let a = list.add_task("A", None).unwrap();
+        let b = list.add_task("B", None).unwrap();
+        let c = list.add_task("C", None).unwrap();
+        list.add_dependency(b, a).unwrap();
+        list.add_dependency(c, b).unwrap();
+
+        assert_eq!(list.add_dependency(a, c), Err(TaskError::DependencyCycle(a, c)));
+        assert_eq!(list.add_dependency(a, a), Err(TaskError::DependencyCycle(a, a)));
+        assert_eq!(TaskError::DependencyCycle(a, a).to_string(), "A task can't depend on itself");
+        assert_eq!(TaskError::DependencyCycle(a, c).to_string(), format!("Task {} already depends on {}", c, a));
+        assert_eq!(list.add_dependency(a, 99), Err(TaskError::NotFound(99)));
+        assert!(list.find(a).unwrap().get_blocked_by().is_empty());
     }
 
     #[test]
-    fn overdue_true() {
-        let deadline = parse_deadline(String::from("2000-01-01"));
-        let entry = Entry::new(0, String::from("test entry"), deadline);
+    fn most_overdue_first() {
+        let mut list = List::new();
+        list.add_task("Missed last week", parse_deadline(String::from("2001-01-08"), 0)).unwrap();
+        list.add_task("Missed long ago", parse_deadline(String::from("2000-01-01"), 0)).unwrap();
+        list.add_task("Missed yesterday", parse_deadline(String::from("2001-01-14"), 0)).unwrap();
+        list.check_overdues();
+
+        let mut stray = Entry::new(3, String::from("Overdue without deadline"), None);
+        stray.status = Status::Overdue;
+        list.push_entry(stray).unwrap();
+        list.add_task("Zebra", None).unwrap();
+        list.add_task("Apples", None).unwrap();
 
-        assert!(entry.is_overdue());
+        let out = render_tasks(&list, &Theme::default(), SortKey::Name, false);
+        let lines = out.lines().collect::<Vec<&str>>();
+        assert_eq!(lines[..3], ["* 1 Missed long ago", "* 0 Missed last week", "* 2 Missed yesterday"]);
+        // Listed and sorted with the todo group instead
+        assert_eq!(lines[3..], ["| 5 Apples", "| 3 Overdue without deadline", "| 4 Zebra"]);
     }
 
     #[test]
-    fn overdue_missing() {
-        // Empty deadline, results in null
-        let deadline = parse_deadline(String::from(""));
-        let entry = Entry::new(0, String::from("test entry"), deadline);
+    fn done_most_recent_first() {
+        let mut list = List::new();
+        for (i, completed) in [None, Some(at(2024, 1, 1)), Some(at(2024, 3, 1)), Some(at(2024, 2, 1))].into_iter().enumerate() {
+            let mut entry = Entry::new(i as i32, format!("Done {}", i), None);
+            entry.status = Status::Done;
+            entry.completed_at = completed;
+            list.push_entry(entry).unwrap();
+        }
 
-        assert!(!entry.is_overdue());
+        let out = render_tasks(&list, &Theme::default(), SortKey::Insertion, false);
+        let done = out.lines().filter(|l| l.starts_with('-')).collect::<Vec<&str>>();
+        assert_eq!(done, vec!["- 2 Done 2", "- 3 Done 3", "- 1 Done 1", "- 0 Done 0"]);
     }
 
     #[test]
-    fn not_overdue() {
-        // One day from now
-        let curr_time = Local::now().naive_local() + Duration::from_secs(60*60*24);
-        let deadline_str = format!("{}-{}-{}", curr_time.year(), curr_time.month(), curr_time.day());
-        let deadline = parse_deadline(String::from(deadline_str));
-        let entry = Entry::new(0, String::from("test entry"), deadline);
+    fn bump_overdue_tasks() {
+        let mut list = List::new();
+        let late = list.add_task("Late", parse_deadline(String::from("2000-01-01"), 0)).unwrap();
+        let later = list.add_task("Later", parse_deadline(String::from("2001-01-01"), 0)).unwrap();
+        let future = list.add_task("Future", parse_deadline(String::from("2080-01-01"), 0)).unwrap();
+        let closed = list.add_task("Closed", parse_deadline(String::from("2000-01-01"), 0)).unwrap();
+        list.check_overdues();
+        list.close_task(closed).unwrap();
+
+        let to = parse_deadline(String::from("2090-06-01"), 0).unwrap();
+        assert_eq!(list.bump_overdue(to), 2);
+        for id in [late, later] {
+            assert_eq!(list.find(id).unwrap().get_status(), &Status::Todo);
+            assert_eq!(list.find(id).unwrap().get_deadline(), Some(to));
+        }
+        assert_eq!(list.find(future).unwrap().get_deadline(), parse_deadline(String::from("2080-01-01"), 0));
+        assert_eq!(list.find(closed).unwrap().get_status(), &Status::Done);
+    }
+
+    #[test]
+    fn page_only_when_needed() {
+        assert!(should_page(true, 50, Some(24), false));
+        assert!(!should_page(true, 10, Some(24), false));
+        assert!(!should_page(true, 24, Some(24), false));
+        assert!(!should_page(false, 50, Some(24), false));
+        assert!(!should_page(true, 50, Some(24), true));
+        assert!(!should_page(true, 50, None, false));
+    }
+
+    #[test]
+    fn reconcile_stale_statuses() {
+        let mut list = List::new();
+        let stale = list.add_task("Stale", parse_deadline(String::from("2000-01-01"), 0)).unwrap();
+        let snoozed = list.add_task("Snoozed", parse_deadline(String::from("2000-01-01"), 0)).unwrap();
+        let closed = list.add_task("Closed", parse_deadline(String::from("2000-01-01"), 0)).unwrap();
+        list.add_task("No deadline", None).unwrap();
+        list.check_overdues();
+        list.close_task(closed).unwrap();
+
+        list.find_mut(snoozed).unwrap().set_deadline(parse_deadline(String::from("2090-01-01"), 0));
+        list.find_mut(stale).unwrap().status = Status::Todo;
+
+        assert_eq!(list.reconcile(), 2);
+        assert_eq!(list.find(stale).unwrap().get_status(), &Status::Overdue);
+        assert_eq!(list.find(snoozed).unwrap().get_status(), &Status::Todo);
+        assert_eq!(list.find(closed).unwrap().get_status(), &Status::Done);
+        assert_eq!(list.reconcile(), 0);
+    }
+
+    #[test]
+    fn duplicate_keeps_relative_deadline() {
+        let mut list = List::new();
+        let id = list.add_task_in("Fortnightly review", parse_offset("+2w").unwrap()).unwrap();
+        list.find_mut(id).unwrap().timestamp -= Duration::days(3);
+        list.find_mut(id).unwrap().deadline = Some(list.find(id).unwrap().timestamp + Duration::weeks(2));
+
+        let copy = list.duplicate_task(id).unwrap();
+        let copy = list.find(copy).unwrap();
+        assert_eq!(copy.get_deadline_offset(), Some(Duration::weeks(2)));
+        assert_eq!(copy.get_deadline(), Some(copy.get_timestamp() + Duration::weeks(2)));
+        assert!(copy.get_deadline() > list.find(id).unwrap().get_deadline());
+
+        let json = serde_json::to_string(&list).unwrap();
+        let restored = read_list(json.as_bytes()).unwrap();
+        assert_eq!(restored.find(id).unwrap().get_deadline_offset(), Some(Duration::weeks(2)));
+    }
+
+    #[test]
+    fn read_list_from_buffer() {
+        let json = br#"{"entries": [
+            {"id": 0, "task": "Water plants", "status": "done", "timestamp": "2024-01-02T09:00:00"},
+            {"id": 4, "task": "Call Bob", "status": "Todo", "timestamp": "2024-01-03T09:00:00", "deadline": "2024-01-05T00:00:00"}
+        ], "id_tracker": 5}"#;
+
+        let list = read_list(&json[..]).unwrap();
+        assert_eq!(list.get_size(), 2);
+        assert_eq!(list.get_cursor(), 5);
+        assert_eq!(*list.find(0).unwrap().get_status(), Status::Done);
+        assert_eq!(list.find(4).unwrap().get_name(), "Call Bob");
+        assert!(list.find(4).unwrap().is_all_day());
+
+        assert_eq!(read_list(&b"{\"entries\": ["[..]).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    fn closed_on(days: &[NaiveDateTime]) -> List {
+        let mut list = List::new();
+        for (i, day) in days.iter().enumerate() {
+            let id = list.add_task(&format!("Task {}", i), None).unwrap();
+            list.close_task(id).unwrap();
+            list.find_mut(id).unwrap().completed_at = Some(*day);
+        }
+        list
+    }
+
+    #[test]
+    fn close_reason_recorded() {
+        let mut list = List::new();
+        let single = list.add_task("By hand", None).unwrap();
+        let tagged = list.add_task("Standup", None).unwrap();
+        list.tag_task(tagged, &["meeting"]).unwrap();
+
+        list.close_task(single).unwrap();
+        list.close_by_tag("meeting");
+        assert_eq!(list.find(single).unwrap().get_completed_via(), Some(CloseReason::Manual));
+        assert_eq!(list.find(tagged).unwrap().get_completed_via(), Some(CloseReason::Bulk));
+
+        let old: Entry = serde_json::from_str(r#"{"id": 0, "task": "Old", "status": "Done", "timestamp": "2024-01-01T00:00:00", "deadline": null}"#).unwrap();
+        assert_eq!(old.get_completed_via(), None);
+    }
+
+    #[test]
+    fn remove_clears_references() {
+        let mut list = List::new();
+        let parent = list.add_task("Move house", None).unwrap();
+        let child = list.add_subtask(parent, "Pack books").unwrap();
+        let waiting = list.add_task("Unpack", None).unwrap();
+        list.add_dependency(waiting, parent).unwrap();
+
+        assert_eq!(list.remove_task(parent).unwrap().get_name(), "Move house");
+        assert!(list.find(parent).is_none());
+        assert_eq!(list.find(child).unwrap().get_parent(), None);
+        assert!(list.find(waiting).unwrap().get_blocked_by().is_empty());
+        assert_eq!(list.get_cursor(), 3);
+        assert_eq!(list.remove_task(parent).unwrap_err(), TaskError::NotFound(parent));
+    }
+
+    #[test]
+    fn reschedule_clears_deadline() {
+        let mut list = List::new();
+        let id = list.add_task("Late", Some(at(2024, 1, 2))).unwrap();
+        list.check_overdues();
+        assert_eq!(list.find(id).unwrap().get_status(), &Status::Overdue);
+
+        list.reschedule(id, None).unwrap();
+        assert_eq!(list.find(id).unwrap().get_deadline(), None);
+        assert_eq!(list.find(id).unwrap().get_status(), &Status::Todo);
+
+        list.reschedule(id, Some(at(2024, 1, 3))).unwrap();
+        assert_eq!(list.find(id).unwrap().get_status(), &Status::Overdue);
+        assert_eq!(list.reschedule(42, None), Err(TaskError::NotFound(42)));
+    }
+
+    #[test]
+    fn due_between_inclusive() {
+        let mut list = List::new();
+        let first = list.add_task("First day", Some(at(2024, 3, 1))).unwrap();
+        let last = list.add_task("Last day", Some(at(2024, 3, 31))).unwrap();
+        let middle = list.add_task("Middle", Some(at(2024, 3, 15))).unwrap();
+        list.add_task("Before", Some(at(2024, 2, 29))).unwrap();
+        list.add_task("After", Some(at(2024, 4, 1))).unwrap();
+        list.add_task("Undated", None).unwrap();
+        let closed = list.add_task("Closed", Some(at(2024, 3, 10))).unwrap();
+        list.close_task(closed).unwrap();
+
+        let start = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let end = NaiveDate::from_ymd_opt(2024, 3, 31).unwrap();
+        let due = list.due_between(start, end).iter().map(|e| e.get_id()).collect::<Vec<i32>>();
+        assert_eq!(due, vec![first, middle, last]);
+        assert!(list.due_between(end, start).is_empty());
+    }
+
+    #[test]
+    fn shift_tagged_deadlines() {
+        let now = Local::now().naive_local();
+        let mut list = List::new();
+        let soon = list.add_task("Soon", Some(now + Duration::days(1))).unwrap();
+        let late = list.add_task("Late", Some(now - Duration::days(2))).unwrap();
+        let other = list.add_task("Other", Some(now + Duration::days(1))).unwrap();
+        let undated = list.add_task("Undated", None).unwrap();
+        for id in [soon, late, undated] {
+            list.tag_task(id, &["q3"]).unwrap();
+        }
+        list.check_overdues();
+        assert_eq!(list.find(late).unwrap().get_status(), &Status::Overdue);
+
+        let shifted = list.shift_deadlines(|e| e.get_tags().iter().any(|t| t == "q3"), Duration::days(7));
+        assert_eq!(shifted, 2);
+        assert_eq!(list.find(soon).unwrap().get_deadline(), Some(now + Duration::days(8)));
+        assert_eq!(list.find(late).unwrap().get_deadline(), Some(now + Duration::days(5)));
+        assert_eq!(list.find(late).unwrap().get_status(), &Status::Todo);
+        assert_eq!(list.find(other).unwrap().get_deadline(), Some(now + Duration::days(1)));
+        assert!(list.find(undated).unwrap().get_deadline().is_none());
+
+        let far = Duration::MAX;
+        assert_eq!(list.shift_deadlines(|e| e.get_tags().iter().any(|t| t == "q3"), far), 0);
+        assert_eq!(list.find(soon).unwrap().get_deadline(), Some(now + Duration::days(8)));
+
+        assert_eq!(parse_shift("-2h"), Some(Duration::hours(-2)));
+        assert_eq!(parse_shift("7d"), None);
+    }
+
+    #[test]
+    fn newer_tasks_on_disk() {
+        let mut loaded = List::new();
+        loaded.add_task("Shared", None).unwrap();
+        loaded.add_task("Removed here", None).unwrap();
+        let mut memory = loaded.clone();
+        memory.entries.retain(|e| e.id != 1);
+        memory.add_task("Mine", None).unwrap();
+        memory.entries[1].timestamp = at(2024, 1, 9);
+
+        let mut on_disk = loaded.clone();
+        assert_eq!(unseen_on_disk(&on_disk, &memory, &loaded), 0);
+
+        on_disk.add_task("Theirs, same id as mine", None).unwrap();
+        on_disk.entries[2].timestamp = at(2024, 1, 10);
+        on_disk.add_task("Also theirs", None).unwrap();
+        assert_eq!(unseen_on_disk(&on_disk, &memory, &loaded), 2);
+    }
+
+    #[test]
+    fn export_sets_last_modified() {
+        let path = temp_path("last-modified.json");
+        export_with(List::new(), &path, None).unwrap();
+
+        let loaded = load_list(&path, None).unwrap();
+        let saved = loaded.get_last_modified().unwrap();
+        assert!(Local::now().naive_local() - saved < Duration::seconds(5));
+        let again = serde_json::from_str::<List>(&serde_json::to_string(&loaded).unwrap()).unwrap();
+        assert_eq!(again.get_last_modified(), Some(saved));
+
+        fs::write(&path, r#"{"entries": [], "id_tracker": 0}"#).unwrap();
+        let backfilled = load_list(&path, None).unwrap().get_last_modified().unwrap();
+        assert!(Local::now().naive_local() - backfilled < Duration::seconds(5));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn file_size_cap() {
+        let path = temp_path("oversized.json");
+        let mut list = List::new();
+        list.add_task("Fits", None).unwrap();
+        export_with(list, &path, None).unwrap();
+
+        let err = check_file_size(&path, 16).unwrap_err();
+        assert!(err.to_string().contains("over the 16 byte limit"));
+        assert!(check_file_size(&path, max_file_bytes()).is_ok());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn task_path_is_directory() {
+        let path = temp_path("dir-tasks.json");
+        fs::create_dir_all(&path).unwrap();
+        let expected = format!("{} is not a regular file", path);
+
+        assert_eq!(read_or_create(&path).unwrap_err().to_string(), expected);
+        assert_eq!(export(List::new(), &path).unwrap_err().to_string(), expected);
+        fs::remove_dir(path).unwrap();
+    }
+
+    #[test]
+    fn template_relative_deadlines() {
+        let path = temp_path("template.json");
+        fs::write(&path, r#"[
+            {"name": "Kickoff", "deadline_offset_days": 2, "priority": "high", "tags": ["project"]},
+            {"name": "Retro", "deadline_offset_days": 14}
+        ]"#).unwrap();
+
+        let mut list = List::new();
+        assert_eq!(list.apply_template(&path).unwrap(), 2);
+
+        let kickoff = &list.get_all()[0];
+        assert_eq!(kickoff.get_deadline().unwrap() - kickoff.get_timestamp(), Duration::days(2));
+        assert_eq!(kickoff.get_priority(), Priority::High);
+        assert_eq!(kickoff.get_tags(), &vec![String::from("project")]);
+
+        let retro = &list.get_all()[1];
+        assert_eq!(retro.get_deadline().unwrap() - retro.get_timestamp(), Duration::days(14));
+        assert_eq!(retro.get_priority(), Priority::Normal);
+
+        fs::write(&path, r#"[{"name": "Fine"}, {"name": " "}]"#).unwrap();
+        assert!(list.apply_template(&path).is_err());
+        fs::write(&path, r#"[{"name": "Fine"}, {"name": "Far", "deadline_offset_days": 9223372036854775807}]"#).unwrap();
+        assert_eq!(list.apply_template(&path).unwrap_err().kind(), ErrorKind::InvalidData);
+        fs::write(&path, r#"[{"name": "Fine"}, {"name": "Far", "deadline_offset_days": 100000000}]"#).unwrap();
+        assert_eq!(list.apply_template(&path).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(list.get_size(), 2);
+        assert_eq!(list.add_task_in("Far", Duration::MAX), Err(TaskError::DeadlineOutOfRange));
+        assert_eq!(list.get_size(), 2);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn menu_positions_map_to_ids() {
+        let ids = [4, 9, 2];
+        assert_eq!(menu_task_id(&ids, "1"), Some(4));
+        assert_eq!(menu_task_id(&ids, " 3\n"), Some(2));
+        assert_eq!(menu_task_id(&ids, "0"), None);
+        assert_eq!(menu_task_id(&ids, "4"), None);
+        assert_eq!(menu_task_id(&ids, "two"), None);
+    }
+
+    #[test]
+    fn colorize_only_when_enabled() {
+        assert_eq!(colorize("Task not found", "31", true), "\x1b[31mTask not found\x1b[0m");
+        assert_eq!(colorize("Task not found", "31", false), "Task not found");
+    }
+
+    #[test]
+    fn autosave_predicate() {
+        let secs = std::time::Duration::from_secs;
+        assert!(should_autosave(secs(30), Some(secs(30)), true));
+        assert!(should_autosave(secs(45), Some(secs(30)), true));
+        assert!(!should_autosave(secs(29), Some(secs(30)), true));
+        assert!(!should_autosave(secs(45), Some(secs(30)), false));
+        assert!(!should_autosave(secs(45), None, true));
+    }
+
+    #[test]
+    fn timing_line_format() {
+        let line = timing_line("load", std::time::Duration::from_micros(1234), 40);
+        assert_eq!(line, "[debug] load: 1.2ms, 40 tasks");
+        assert_eq!(timing_line("save", std::time::Duration::from_secs(2), 1), "[debug] save: 2.0s, 1 task");
+    }
+
+    #[test]
+    fn created_weekday_buckets() {
+        let mut list = List::new();
+        // 2024-01-08 is a Monday
+        for day in [8, 10, 10, 14, 15] {
+            let id = list.add_task(&format!("Day {}", day), None).unwrap();
+            list.find_mut(id).unwrap().timestamp = at(2024, 1, day);
+        }
+
+        let counts = list.created_by_weekday();
+        assert_eq!(counts, [2, 0, 2, 0, 0, 0, 1]);
+        assert!(habits_chart(&counts).starts_with("Mon ## 2\nTue  0\nWed ## 2"));
+    }
+
+    #[test]
+    fn streak_three_days() {
+        let list = closed_on(&[at(2024, 1, 8), at(2024, 1, 9), at(2024, 1, 10), at(2024, 1, 10)]);
+        assert_eq!(list.current_streak_at(reference_now().date()), 3);
+        assert_eq!(streak_message(3, "🔥"), Some(String::from("🔥 3-day streak!")));
+    }
+
+    #[test]
+    fn streak_broken_by_gap() {
+        let list = closed_on(&[at(2024, 1, 6), at(2024, 1, 7), at(2024, 1, 9), at(2024, 1, 10)]);
+        assert_eq!(list.current_streak_at(reference_now().date()), 2);
+
+        let list = closed_on(&[at(2024, 1, 8), at(2024, 1, 9)]);
+        assert_eq!(list.current_streak_at(reference_now().date()), 0);
+    }
+
+    #[test]
+    fn streak_without_completions() {
+        let mut list = List::new();
+        list.add_task("Open", None).unwrap();
+        assert_eq!(list.current_streak_at(reference_now().date()), 0);
+        assert_eq!(streak_message(1, "🔥"), None);
+    }
+
+    #[test]
+    fn someday_filter() {
+        let mut list = List::new();
+        let idea = list.add_someday("Learn the cello").unwrap();
+        let dated = list.add_task("Low with deadline", Some(at(2030, 1, 1))).unwrap();
+        list.set_priority(dated, Priority::Low).unwrap();
+        list.add_task("Normal", None).unwrap();
+        let closed = list.add_someday("Closed idea").unwrap();
+        list.close_task(closed).unwrap();
+
+        let ids = list.someday().iter().map(|e| e.get_id()).collect::<Vec<i32>>();
+        assert_eq!(ids, vec![idea]);
+
+        let out = render_tasks(&list, &Theme::default(), SortKey::Insertion, false);
+        assert!(!out.contains("Learn the cello"));
+        assert!(out.contains("1 task in Someday/Maybe"));
+    }
+
+    #[test]
+    fn id_range_hint() {
+        let mut list = List::new();
+        assert_eq!(list.check_id(0).unwrap_err().to_string(), "No task #0 (no tasks yet)");
+
+        for name in ["First", "Second", "Third"] {
+            list.add_task(name, None).unwrap();
+        }
+        assert_eq!(list.check_id(1), Ok(()));
+        assert_eq!(list.check_id(3), Err(TaskError::OutOfRange { id: 3, min: 0, cursor: 3 }));
+        assert_eq!(list.check_id(7).unwrap_err().to_string(), "No task #7 (valid range 0..3)");
+        assert_eq!(list.check_id(-1).unwrap_err().to_string(), "No task #-1 (valid range 0..3)");
+    }
+
+    #[test]
+    fn oneline_fields() {
+        let mut list = List::new();
+        list.add_task("Call Bob", Some(at(2030, 1, 2))).unwrap();
+        list.add_task("Water plants", None).unwrap();
+        let late = list.add_task("Pay rent", parse_deadline(String::from("2000-01-01"), 0)).unwrap();
+        list.close_task(1).unwrap();
+        list.check_overdues();
+
+        assert_eq!(
+            render_oneline(&list),
+            format!("{}\toverdue\t2000-01-01T00:00\tPay rent\n0\ttodo\t2030-01-02T12:00\tCall Bob\n1\tdone\t-\tWater plants\n", late)
+        );
+    }
+
+    #[test]
+    fn default_order_composite() {
+        let mut list = List::new();
+        let normal_late = list.add_task("Normal late", Some(at(2030, 3, 1))).unwrap();
+        let low = list.add_task("Low soon", Some(at(2030, 1, 1))).unwrap();
+        let high_undated = list.add_task("High undated", None).unwrap();
+        let normal_soon = list.add_task("Normal soon", Some(at(2030, 2, 1))).unwrap();
+        let high_dated = list.add_task("High dated", Some(at(2030, 6, 1))).unwrap();
+        let normal_undated = list.add_task("Normal undated", None).unwrap();
+        for (id, priority) in [(low, Priority::Low), (high_undated, Priority::High), (high_dated, Priority::High)] {
+            list.set_priority(id, priority).unwrap();
+        }
+        list.find_mut(normal_undated).unwrap().timestamp = at(2020, 1, 1);
+
+        let ids = list.default_order().iter().map(|e| e.get_id()).collect::<Vec<i32>>();
+        assert_eq!(ids, vec![high_dated, high_undated, normal_soon, normal_late, normal_undated, low]);
+
+        let mut entries = list.snapshot();
+        SortKey::Priority.sort(&mut entries);
+        assert_eq!(entries.iter().map(|e| e.get_id()).collect::<Vec<i32>>(), ids);
+    }
+
+    #[test]
+    fn snapshot_is_detached() {
+        let mut list = List::new();
+        list.add_task("First", None).unwrap();
+        list.add_task("Second", None).unwrap();
+
+        let snapshot = list.snapshot();
+        list.close_task(0).unwrap();
+        list.rename_task(1, "Renamed").unwrap();
+        list.add_task("Third", None).unwrap();
+
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(*snapshot[0].get_status(), Status::Todo);
+        assert_eq!(snapshot[1].get_name(), "Second");
+    }
+
+    #[test]
+    fn push_entry_advances_cursor() {
+        let mut list = List::new();
+        list.add_task("First", None).unwrap();
+
+        assert_eq!(list.push_entry(Entry::new(7, String::from("Imported"), None)), Ok(7));
+        assert_eq!(list.get_cursor(), 8);
+        assert_eq!(list.add_task("Next", None), Ok(8));
+
+        assert_eq!(list.push_entry(Entry::new(3, String::from("Older"), None)), Ok(3));
+        assert_eq!(list.get_cursor(), 9);
+        assert_eq!(list.push_entry(Entry::new(7, String::from("Again"), None)), Err(TaskError::DuplicateId(7)));
+
+        assert_eq!(list.push_entry(Entry::new(i32::MAX, String::from("Huge"), None)), Err(TaskError::IdOverflow(i32::MAX)));
+        assert_eq!(list.get_cursor(), 9);
+        assert!(list.find(i32::MAX).is_none());
+    }
+
+    #[test]
+    fn enums_lowercase_with_old_names() {
+        assert_eq!(serde_json::to_string(&Status::InProgress).unwrap(), r#""inprogress""#);
+        assert_eq!(serde_json::to_string(&Priority::High).unwrap(), r#""high""#);
+
+        for (old, new, status) in [("Done", "done", Status::Done), ("InProgress", "inprogress", Status::InProgress)] {
+            assert_eq!(serde_json::from_str::<Status>(&format!("\"{}\"", old)).unwrap(), status);
+            assert_eq!(serde_json::from_str::<Status>(&format!("\"{}\"", new)).unwrap(), status);
+        }
+        assert_eq!(serde_json::from_str::<Priority>(r#""Low""#).unwrap(), Priority::Low);
+        assert_eq!(serde_json::from_str::<Priority>(r#""low""#).unwrap(), Priority::Low);
+    }
+
+    #[test]
+    fn filter_high_priority() {
+        let mut list = List::new();
+        let urgent = list.add_task("Urgent", None).unwrap();
+        list.add_task("Whenever", None).unwrap();
+        let closed = list.add_task("Urgent but done", None).unwrap();
+        let sooner = list.add_task("Urgent and soon", parse_deadline(String::from("2030-01-01"), 0)).unwrap();
+        for id in [urgent, closed, sooner] {
+            list.set_priority(id, Priority::High).unwrap();
+        }
+        list.close_task(closed).unwrap();
+
+        let high = list.filter_by_priority(Priority::High).iter().map(|e| e.get_id()).collect::<Vec<i32>>();
+        assert_eq!(high, vec![sooner, urgent]);
+        assert!(list.set_priority(99, Priority::Low).is_err());
+    }
+
+    #[test]
+    fn start_of_day_dst_gap() {
+        // Clocks in Sao Paulo jumped from midnight to 01:00 on 2018-11-04
+        let date = NaiveDate::from_ymd_opt(2018, 11, 4).unwrap();
+        let start = start_of_day(&chrono_tz::America::Sao_Paulo, date).unwrap();
+        assert_eq!(start, date.and_hms_opt(1, 0, 0).unwrap());
+
+        let normal = NaiveDate::from_ymd_opt(2018, 11, 5).unwrap();
+        let start = start_of_day(&chrono_tz::America::Sao_Paulo, normal).unwrap();
+        assert_eq!(start, normal.and_hms_opt(0, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn random_pending_seeded() {
+        let mut list = List::new();
+        assert!(list.random_pending().is_none());
+
+        let closed = list.add_task("Closed", None).unwrap();
+        list.close_task(closed).unwrap();
+        assert!(list.random_pending().is_none());
+
+        list.add_task("First", None).unwrap();
+        list.add_task("Second", None).unwrap();
+        let mut rng = XorShift::new(42);
+        for _ in 0..20 {
+            let picked = list.random_pending_with(&mut rng).unwrap();
+            assert_ne!(picked.get_id(), closed);
+        }
+
+        let first = list.random_pending_with(&mut XorShift::new(7)).unwrap().get_id();
+        assert_eq!(list.random_pending_with(&mut XorShift::new(7)).unwrap().get_id(), first);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn export_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = temp_path("private.json");
+        fs::write(&path, "{}").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+
+        let mut list = List::new();
+        list.add_task("Secret", None).unwrap();
+        export_with(list, &path, None).unwrap();
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(load_list(&path, None).unwrap().get_size(), 1);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn all_day_vs_timed() {
+        let day = NaiveDate::from_ymd_opt(2024, 3, 5).unwrap();
+        let all_day = Entry::new(0, String::from("Submit report"), day.and_hms_opt(0, 0, 0));
+        let timed = Entry::new(1, String::from("Call at 3pm"), day.and_hms_opt(15, 0, 0));
+        assert!(all_day.is_all_day());
+        assert!(!timed.is_all_day());
+
+        let before = day.and_hms_opt(14, 59, 0).unwrap();
+        let after = day.and_hms_opt(15, 1, 0).unwrap();
+        let next_day = day.and_hms_opt(23, 59, 0).unwrap() + Duration::minutes(2);
+        assert!(!timed.is_overdue_at(before, Duration::zero(), 0));
+        assert!(timed.is_overdue_at(after, Duration::zero(), 0));
+        assert!(!all_day.is_overdue_at(after, Duration::zero(), 0));
+        assert!(all_day.is_overdue_at(next_day, Duration::zero(), 0));
+    }
+
+    #[test]
+    fn night_owl_day_start() {
+        let night = NaiveDate::from_ymd_opt(2024, 1, 11).unwrap().and_hms_opt(2, 0, 0).unwrap();
+        assert_eq!(logical_today(night, 4), NaiveDate::from_ymd_opt(2024, 1, 10).unwrap());
+        assert_eq!(logical_today(night, 0), NaiveDate::from_ymd_opt(2024, 1, 11).unwrap());
+        assert_eq!(logical_today(night + Duration::hours(2), 4), NaiveDate::from_ymd_opt(2024, 1, 11).unwrap());
+
+        let all_day = Entry::new(0, String::from("Submit report"), NaiveDate::from_ymd_opt(2024, 1, 10).unwrap().and_hms_opt(0, 0, 0));
+        assert_eq!(all_day.due_at_with(4), NaiveDate::from_ymd_opt(2024, 1, 11).unwrap().and_hms_opt(4, 0, 0));
+        assert!(all_day.due_at_with(4).unwrap() > night);
+
+        let mut list = List::new();
+        list.set_day_start_hour(4);
+        let id = list.add_task("Submit report", NaiveDate::from_ymd_opt(2024, 1, 10).unwrap().and_hms_opt(0, 0, 0)).unwrap();
+        assert_eq!(list.stale_overdues_at(night, Duration::zero()), 0);
+        assert_eq!(list.stale_overdues_at(night + Duration::hours(3), Duration::zero()), 1);
+        list.close_task(id).unwrap();
+        list.entries[0].completed_at = Some(night);
+        assert_eq!(list.current_streak_at(NaiveDate::from_ymd_opt(2024, 1, 10).unwrap()), 1);
+    }
+
+    #[test]
+    fn all_day_inferred_from_old_json() {
+        let json = r#"{"id":0,"task":"Old","status":"Todo","timestamp":"2024-01-01T00:00:00","deadline":"2024-03-05T00:00:00"}"#;
+        let entry: Entry = serde_json::from_str(json).unwrap();
+        assert!(entry.is_all_day());
+
+        let json = json.replace("2024-03-05T00:00:00", "2024-03-05T09:30:00");
+        let entry: Entry = serde_json::from_str(&json).unwrap();
+        assert!(!entry.is_all_day());
+    }
+
+    struct RecordingRunner {
+        commands: std::cell::RefCell<Vec<String>>,
+        fail: bool
+    }
+
+    impl CommandRunner for RecordingRunner {
+        fn run(&self, command: &str) -> Result<(), Error> {
+            self.commands.borrow_mut().push(command.to_string());
+            if self.fail {
+                return Err(Error::new(ErrorKind::NotFound, "no shell"))
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn on_complete_expands_task() {
+        let runner = RecordingRunner { commands: std::cell::RefCell::new(Vec::new()), fail: false };
+        run_on_complete(Some("habit log {task}"), "Go running", &runner);
+        run_on_complete(Some("echo {task}"), "Bob's call; rm -rf /", &runner);
+        run_on_complete(None, "Ignored", &runner);
+
+        assert_eq!(*runner.commands.borrow(), vec![
+            String::from("habit log 'Go running'"),
+            String::from("echo 'Bob'\\''s call; rm -rf /'")
+        ]);
+
+        let failing = RecordingRunner { commands: std::cell::RefCell::new(Vec::new()), fail: true };
+        run_on_complete(Some("habit log {task}"), "Go running", &failing);
+        assert_eq!(failing.commands.borrow().len(), 1);
+    }
+
+    #[test]
+    fn humanize_ago_intervals() {
+        let now = at(2024, 6, 30);
+        assert_eq!(humanize_ago(now - Duration::seconds(20), now), "just now");
+        assert_eq!(humanize_ago(now - Duration::minutes(5), now), "5m ago");
+        assert_eq!(humanize_ago(now - Duration::hours(2), now), "2h ago");
+        assert_eq!(humanize_ago(now - Duration::days(3), now), "3d ago");
+        assert_eq!(humanize_ago(now - Duration::days(15), now), "2w ago");
+        assert_eq!(humanize_ago(now - Duration::weeks(8), now), "8w ago");
+        assert_eq!(humanize_ago(at(2024, 1, 2), now), "2024-01-02");
+    }
+
+    #[test]
+    fn done_vs_open_split() {
+        let mut list = List::new();
+        assert_eq!(list.done_vs_open(), (0, 0));
+        assert_eq!(overall_progress(&list), "0/0 done [----------] 0%");
+
+        let closed = list.add_task("Closed", None).unwrap();
+        let started = list.add_task("Started", None).unwrap();
+        list.add_task("Late", parse_deadline(String::from("2000-01-01"), 0)).unwrap();
+        list.add_task("Todo", None).unwrap();
+        list.check_overdues();
+        list.close_task(closed).unwrap();
+        list.start_task(started).unwrap();
+
+        assert_eq!(list.done_vs_open(), (1, 3));
+        assert_eq!(overall_progress(&list), "1/4 done [###-------] 25%");
+
+        for n in 0..300 {
+            list.add_task(&format!("Bulk {}", n), None).unwrap();
+        }
+        assert_eq!(list.done_vs_open(), (1, 303));
+    }
+
+    #[test]
+    fn wip_limit_threshold() {
+        assert!(!over_wip_limit(2, Some(3)));
+        assert!(!over_wip_limit(3, Some(3)));
+        assert!(over_wip_limit(4, Some(3)));
+        assert!(!over_wip_limit(100, None));
+    }
+
+    #[test]
+    fn pluralize_counts() {
+        assert_eq!(pluralize(0, "task", "tasks"), "0 tasks");
+        assert_eq!(pluralize(1, "task", "tasks"), "1 task");
+        assert_eq!(pluralize(2, "task", "tasks"), "2 tasks");
+    }
+
+    #[test]
+    fn find_mut_persists() {
+        let mut list = List::new();
+        let id = list.add_task("Write report", None).unwrap();
+
+        list.find_mut(id).unwrap().progress = Some(30);
+        assert_eq!(list.find(id).unwrap().get_progress(), Some(30));
+        assert!(list.find_mut(id + 1).is_none());
+    }
+
+    #[test]
+    fn close_by_name_unique() {
+        let mut list = List::new();
+        list.add_task("Buy milk", None).unwrap();
+        list.add_task("Call Bob", None).unwrap();
+
+        let id = list.close_by_name("call bob").unwrap();
+
+        assert_eq!(id, 1);
+        assert_eq!(list.get_all()[1].status, Status::Done);
+        assert_eq!(list.get_all()[0].status, Status::Todo);
+    }
+
+    #[test]
+    fn close_by_name_ambiguous() {
+        let mut list = List::new();
+        list.add_task("Buy milk", None).unwrap();
+        list.add_task("buy MILK", None).unwrap();
+
+        let res = list.close_by_name("Buy milk");
 
-        assert!(!entry.is_overdue());
+        assert_eq!(res, Err(TaskError::Ambiguous(String::from("Buy milk"), 2)));
+        assert_eq!(list.get_status()[&Status::Done], 0);
     }
 }
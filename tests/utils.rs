@@ -3,11 +3,11 @@ use todo::utils::*;
 fn get_stub() -> List {
     let mut to_return = List::new();
 
-    to_return.add_task("Done entry", None);
-    to_return.add_task("Done entry 2", None);
+    to_return.add_task("Done entry", None).unwrap();
+    to_return.add_task("Done entry 2", None).unwrap();
 
-    to_return.add_task("Sample entry", None);
-    to_return.add_task("Sample entry 2", None);
+    to_return.add_task("Sample entry", None).unwrap();
+    to_return.add_task("Sample entry 2", None).unwrap();
 
     to_return
 }
@@ -24,3 +24,17 @@ fn status_dist() {
     assert_eq!(res[&Status::Todo], 2);
     assert_eq!(res[&Status::Done], 2);
 }
+
+#[test]
+fn mutate_through_accessor() {
+    let mut list = get_stub();
+    let deadline = parse_deadline(String::from("2030-06-01"), 0);
+
+    for entry in list.entries_mut().filter(|e| e.get_id() == 2) {
+        entry.set_deadline(deadline);
+    }
+
+    assert_eq!(list.get_all()[2].get_deadline(), deadline);
+    assert_eq!(list.get_all()[3].get_deadline(), None);
+    assert_eq!(list.get_size(), 4);
+}